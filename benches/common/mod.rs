@@ -0,0 +1,77 @@
+//! Input generators shared by the benchmarks.
+//!
+//! All inputs are synthesized deterministically so that results are comparable across commits.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+pub fn module(body: &str) -> String {
+    format!("-module(bench).\n\nfoo() ->\n    {}.\n", body)
+}
+
+pub fn nested_terms(depth: usize) -> String {
+    if depth == 0 {
+        return "leaf".to_owned();
+    }
+    format!(
+        "{{key_{depth}, #{{name => {}, values => [{depth}, {depth}]}}, {}}}",
+        nested_terms(depth - 1),
+        nested_terms(depth / 2)
+    )
+}
+
+pub fn nested_calls(depth: usize) -> String {
+    if depth == 0 {
+        return "leaf".to_owned();
+    }
+    format!("foo_{depth}({}, bar, [1, 2, 3])", nested_calls(depth - 1))
+}
+
+pub fn long_list(len: usize) -> String {
+    let items = (0..len)
+        .map(|i| format!("{{item_{i}, \"value\", {i}}}"))
+        .collect::<Vec<_>>();
+    format!("[{}]", items.join(", "))
+}
+
+/// A module that has `n` functions, each of which calls several macros.
+pub fn many_macros(n: usize) -> String {
+    let mut text = String::from(concat!(
+        "-module(bench).\n\n",
+        "-define(LOG(Fmt, Args), logger:info(Fmt, Args, #{mfa => {?MODULE, ?FUNCTION_NAME, ?FUNCTION_ARITY}})).\n",
+        "-define(ID(X), X).\n",
+        "-define(PAIR(A, B), {?ID(A), ?ID(B)}).\n",
+        "-define(DEFAULT, [1, 2, 3]).\n\n",
+    ));
+    for i in 0..n {
+        text.push_str(&format!(
+            "foo_{i}(A) ->\n    ?LOG(\"foo ~p\", [A]),\n    ?PAIR(A, ?DEFAULT).\n\n"
+        ));
+    }
+    text
+}
+
+/// The same module as [many_macros] but with the macro calls expanded by hand.
+///
+/// The difference between the parse times of the two modules is the cost of the macro expansions.
+pub fn many_macros_expanded(n: usize) -> String {
+    let mut text = String::from("-module(bench).\n\n");
+    for i in 0..n {
+        text.push_str(&format!(
+            "foo_{i}(A) ->\n    logger:info(\"foo ~p\", [A], #{{mfa => {{bench, foo_{i}, 1}}}}),\n    {{A, [1, 2, 3]}}.\n\n"
+        ));
+    }
+    text
+}
+
+/// The Erlang files under `tests/testdata/`.
+pub fn testdata_files() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/");
+    let mut files = std::fs::read_dir(dir)
+        .expect("failed to read `tests/testdata/`")
+        .map(|entry| entry.expect("failed to read a directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "erl"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
@@ -0,0 +1,181 @@
+//! Benchmarks that measure each formatting phase separately.
+//!
+//! - `tokenize`: lexing by `erl_tokenize`
+//! - `parse`: building items from a `TokenStream` (including macro expansions)
+//! - `macro_expansion`: parsing macro-heavy modules (`with_macros`) and the same modules with the macros expanded
+//!   by hand (`without_macros`); the difference between the two is the cost of the expansions.
+//!   The throughput (functions per second) stays flat from 100 to 50000 functions if the expansion scales linearly
+//! - `build`: building the region tree by `Formatter`
+//! - `write`: writing the region tree into a string
+//! - `layout`: writing the region trees of deeply nested inputs, where many region layouts are retried
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use efmt::format::{Format, Formatter};
+use efmt::items::Module;
+use efmt::parse::{IncludeOptions, TokenStream};
+use erl_tokenize::Tokenizer;
+
+mod common;
+
+const MAX_COLUMNS: usize = 100;
+const LAYOUT_MAX_COLUMNS: usize = 50;
+
+fn inputs() -> Vec<(String, String)> {
+    let mut inputs = common::testdata_files()
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .expect("unreachable")
+                .to_owned();
+            let text = std::fs::read_to_string(&path).expect("failed to read a testdata file");
+            (name, text)
+        })
+        .collect::<Vec<_>>();
+    inputs.push((
+        "long_list_10000".to_owned(),
+        common::module(&common::long_list(10000)),
+    ));
+    inputs.push((
+        "nested_terms_14".to_owned(),
+        common::module(&common::nested_terms(14)),
+    ));
+    inputs.push(("many_macros_1000".to_owned(), common::many_macros(1000)));
+    inputs
+}
+
+fn token_stream(text: &str) -> TokenStream {
+    let tokenizer = Tokenizer::new(text.to_owned());
+    TokenStream::new(tokenizer, IncludeOptions::new().disable_include())
+}
+
+fn parse(text: &str) -> (TokenStream, Module) {
+    let mut ts = token_stream(text);
+    let module = ts.parse().expect("failed to parse");
+    (ts, module)
+}
+
+fn build(text: &str) -> Formatter {
+    let (ts, module) = parse(text);
+    let mut formatter = Formatter::new(ts);
+    module.format(&mut formatter);
+    formatter
+}
+
+fn bench_tokenize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tokenize");
+    for (name, text) in inputs() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &text, |b, text| {
+            b.iter(|| {
+                Tokenizer::new(text.as_str())
+                    .collect::<Result<Vec<_>, _>>()
+                    .expect("failed to tokenize")
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, text) in inputs() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &text, |b, text| {
+            b.iter(|| parse(text))
+        });
+    }
+    group.finish();
+}
+
+fn bench_macro_expansion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("macro_expansion");
+    group.sample_size(10);
+    for n in [100, 1000, 5000, 50000] {
+        group.throughput(Throughput::Elements(n as u64));
+        let inputs = [
+            ("with_macros", common::many_macros(n)),
+            ("without_macros", common::many_macros_expanded(n)),
+        ];
+        for (name, text) in &inputs {
+            group.bench_with_input(BenchmarkId::new(*name, n), text, |b, text| {
+                b.iter(|| parse(text))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for (name, text) in inputs() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &text, |b, text| {
+            b.iter_batched(
+                || parse(text),
+                |(ts, module)| {
+                    let mut formatter = Formatter::new(ts);
+                    module.format(&mut formatter);
+                    formatter
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    for (name, text) in inputs() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &text, |b, text| {
+            b.iter_batched(
+                || build(text),
+                |formatter| formatter.format(MAX_COLUMNS, false),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_layout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("layout");
+    let inputs = [
+        ("nested_terms", 8, common::module(&common::nested_terms(8))),
+        (
+            "nested_terms",
+            12,
+            common::module(&common::nested_terms(12)),
+        ),
+        (
+            "nested_calls",
+            10,
+            common::module(&common::nested_calls(10)),
+        ),
+        (
+            "nested_calls",
+            20,
+            common::module(&common::nested_calls(20)),
+        ),
+        ("long_list", 1000, common::module(&common::long_list(1000))),
+    ];
+    for (name, size, text) in &inputs {
+        group.bench_with_input(BenchmarkId::new(*name, size), text, |b, text| {
+            b.iter_batched(
+                || build(text),
+                |formatter| formatter.format(LAYOUT_MAX_COLUMNS, false),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_tokenize,
+    bench_parse,
+    bench_macro_expansion,
+    bench_build,
+    bench_write,
+    bench_layout
+);
+criterion_main!(benches);
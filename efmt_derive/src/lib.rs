@@ -180,7 +180,12 @@ pub fn derive_format_trait(input: proc_macro::TokenStream) -> proc_macro::TokenS
     let name = input.ident;
     let generics = add_format_trait_bounds(input.generics);
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    let format = generate_format_method_body(&input.data);
+    let mut format = generate_format_method_body(&input.data);
+    if matches!(input.data, Data::Struct(_)) && generics.type_params().next().is_none() {
+        // Enums and generic structs (e.g., `Maybe<T>`) are not recorded as they are too general to explain a layout.
+        let kind = name.to_string();
+        format = with_item_kind(quote! { crate::format::ItemKind::new(#kind) }, format);
+    }
     let expanded = quote! {
         impl #impl_generics crate::format::Format for #name #ty_generics #where_clause {
             fn format(&self, fmt: &mut crate::format::Formatter)  {
@@ -191,6 +196,52 @@ pub fn derive_format_trait(input: proc_macro::TokenStream) -> proc_macro::TokenS
     proc_macro::TokenStream::from(expanded)
 }
 
+/// Records the kind of the item formatted by a hand-written `Format::format()` (or similar) method
+/// for the explain mode, in the same way as `#[derive(Format)]` does for structs.
+///
+/// The method needs to take the formatter as `fmt`.
+/// A detail can be added to the kind (e.g., `#[item_kind("BinaryOpCallExpr", detail = self.op())]`).
+#[proc_macro_attribute]
+pub fn item_kind(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let parser = |input: syn::parse::ParseStream| {
+        let name: syn::LitStr = input.parse()?;
+        let mut kind = quote! { crate::format::ItemKind::new(#name) };
+        if !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            let key: syn::Ident = input.parse()?;
+            if key != "detail" {
+                return Err(syn::Error::new(key.span(), "expected `detail = ...`"));
+            }
+            input.parse::<syn::Token![=]>()?;
+            let detail: TokenStream = input.parse()?;
+            kind = quote! { #kind.detail(#detail) };
+        }
+        Ok(kind)
+    };
+    let kind = parse_macro_input!(attr with parser);
+
+    let mut item = TokenStream::from(item).into_iter().collect::<Vec<_>>();
+    match item.pop() {
+        Some(proc_macro2::TokenTree::Group(body))
+            if body.delimiter() == proc_macro2::Delimiter::Brace =>
+        {
+            let body = with_item_kind(kind, body.stream());
+            item.push(proc_macro2::Group::new(proc_macro2::Delimiter::Brace, body).into());
+        }
+        _ => panic!("`#[item_kind]` can only be applied to a method"),
+    }
+    proc_macro::TokenStream::from(item.into_iter().collect::<TokenStream>())
+}
+
+fn with_item_kind(kind: TokenStream, body: TokenStream) -> TokenStream {
+    quote! {
+        fmt.with_item_kind(#kind, |fmt| { #body });
+    }
+}
+
 fn add_format_trait_bounds(mut generics: Generics) -> Generics {
     for param in &mut generics.params {
         if let GenericParam::Type(ref mut type_param) = *param {
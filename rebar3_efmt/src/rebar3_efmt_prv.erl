@@ -29,12 +29,7 @@ init(State) ->
 do(State) ->
     ok = ensure_efmt_installed(),
     Args0 = rebar_state:command_args(State),
-    Args1 = [case Entry of
-                 {K, V} ->
-                     atom_to_arg_key(K) ++ "=" ++ term_to_arg_value(V);
-                 Flag ->
-                     atom_to_arg_key(Flag)
-             end || Entry <- rebar_state:get(State, efmt, [])] ++ Args0,
+    Args1 = lists:flatmap(fun entry_to_args/1, rebar_state:get(State, efmt, [])) ++ Args0,
     ok = rebar3_efmt_command:execute(Args1),
     {ok, State}.
 
@@ -55,11 +50,26 @@ opts() ->
       "If so, exits with 0. Otherwise, exits with 1 and shows a diff."},
      {write, $w, "write", undefined, "Overwrites input file with the formatted text"},
      {show_files, undefined, "show-files", undefined, "Shows the target input files"},
+     {exclude, undefined, "exclude", string,
+      "Excludes the files matching the given pattern from the default target files and directory arguments "
+      "(this option can be specified multiple times). "
+      "The pattern is written in the gitignore syntax (e.g., `_build/` or `*_parser.erl`). "
+      "The pattern is relative to the current directory. "
+      "The patterns in the `.efmtignore` files in the root directory of the git repository, the current directory "
+      "and the directory arguments are also applied (relative to the directories containing them)"},
      {print_width, undefined, "print-width", integer,
       "Maximum line length. "
       "Note that this is a soft limit. "
       "Ths is, some lines could exceed the limit after formatting. "
       "Besides, this limit doesn't apply to comments. [default: 120]"},
+     {column_width, undefined, "column-width", string,
+      "How to count the width of a text in columns (unicode, chars or bytes). "
+      "`unicode` counts the display width of each grapheme cluster "
+      "(e.g., CJK characters take two columns and combining characters don't take a column by themselves). "
+      "`chars` and `bytes` are provided for compatibility. [default: unicode]"},
+     {stdin_filepath, undefined, "stdin-filepath", string,
+      "The file path of the text given via the standard input (i.e., `-`). "
+      "The path is used to search for include files and in error messages, but the file itself is not read"},
      {verbose, undefined, "verbose", undefined, "Outputs debug log messages"},
      {parallel, undefined, "parallel", undefined, "Executes formatting in parallel"},
      {include_dirs, $I, "include-search-dir", string,
@@ -73,11 +83,51 @@ opts() ->
      {include_cache_dirs, undefined, "include-cache-dir", string,
       "Where to save the caches for the macro definitions collected during processing "
       "`-include` or `-include_lib` directives [default: .efmt/cache]"},
+     {otp_release, undefined, "otp-release", integer,
+      "OTP release number used as the value of `?OTP_RELEASE` macro. "
+      "This also affects the values of `?FEATURE_AVAILABLE(..)` and `?FEATURE_ENABLED(..)` macros [default: the latest OTP release supported by efmt]"},
+     {align_trailing_comments, undefined, "align-trailing-comments", undefined,
+      "Aligns trailing comments on consecutive lines to the same column. "
+      "If an aligned comment exceeds the print width, two spaces are put before the comment instead"},
+     {reflow_comments, undefined, "reflow-comments", undefined,
+      "Rewraps overlong `%%` comment blocks to fit in the print width. "
+      "Single `%` comments, `%%%` comments, the structure of EDoc tags and comments between `efmt:off` and `efmt:on` are kept as they are"},
+     {normalize_blank_lines, undefined, "normalize-blank-lines", undefined,
+      "Puts exactly one blank line between function definitions. "
+      "A spec and the following function definition are kept together. "
+      "Blank lines between other forms (e.g., `-export` or `-include` attributes) are preserved as they are"},
+     {remove_clause_body_leading_blank_lines, undefined, "remove-clause-body-leading-blank-lines", undefined,
+      "Removes blank lines at the start of clause bodies"},
+     {allow_partial, undefined, "allow-partial", undefined,
+      "Keeps the forms that could not be parsed as they are, instead of failing to format the whole file. "
+      "A form is assumed to end with a `.` that is followed by a newline and a line starting at column 0. "
+      "The parse errors of the skipped forms are reported as warnings. "
+      "To overwrite files with `--write`, `--allow-partial-write` must also be specified"},
+     {allow_partial_write, undefined, "allow-partial-write", undefined,
+      "Allows `--write` to overwrite files containing forms that could not be parsed (requires `--allow-partial`)"},
+     {report_missing, undefined, "report-missing", undefined,
+      "Shows a summary of the unresolved `-include` and `-include_lib` files and the undefined macros "
+      "with the files using them at the end of the run"},
+     {deny_missing_macros, undefined, "deny-missing-macros", undefined,
+      "Fails to format the files that use undefined macros or include files that could not be resolved. "
+      "Note that all macros defined in include files are undefined if `--disable-include` is specified"},
+     {rewrite, undefined, "rewrite", string,
+      "Enables a rewrite rule (this option can be specified multiple times). "
+      "Unlike the other options, rewrite rules change the token sequence of the formatted text. "
+      "Available rules: spec-parens, concat-strings, redundant-parens, sort-exports and sort-includes. "
+      "In `rebar.config`, the rules can be enabled by `{rewrite, [spec_parens, ...]}` in the `efmt` entry"},
+     {explain, undefined, "explain", string,
+      "Explains why the given line of the formatted text of the file is laid out as it is (e.g., `src/foo.erl:12`). "
+      "Note that the line number is of the formatted text (i.e., the output of `efmt FILE`), not of the original file. "
+      "This shows the item kinds enclosing the line, the `Indent` and `Newline` policies of the regions in the line "
+      "and whether the regions fell back to another layout due to `LineTooLong` or `MultiLine`"},
      {files, undefined, undefined, string,
       "Format target files. "
+      "Directories are replaced with the target files in them (in the same way as the default target files). "
       "If no files are specified and any of `-c`, `-w` or `--show-files` options is specified, "
-      "All of the files named `**.{hrl,erl,app.src}` and `**/rebar.config` are used as the default "
-      "(note that files spcified by `.gitignore` will be ignored)"}
+      "All of the files named `**.{hrl,erl,app.src,escript,config.src,app,appup,relup}` are used as the default "
+      "(note that files spcified by `.gitignore` will be ignored). "
+      "`*.config` files are included only if they are `{rebar,sys,relx,elvis}.config` or located in `config/` directories"}
     ].
 
 -spec ensure_efmt_installed() -> ok.
@@ -115,6 +165,15 @@ ensure_efmt_installed() ->
             end
     end.
 
+-spec entry_to_args(term()) -> [string()].
+entry_to_args({rewrite, Rules}) when is_list(Rules) ->
+    %% e.g., `{rewrite, [spec_parens, sort_exports]}' enables the `spec-parens' and `sort-exports' rules.
+    ["--rewrite=" ++ lists:flatten(string:replace(atom_to_list(Rule), "_", "-", all)) || Rule <- Rules];
+entry_to_args({K, V}) ->
+    [atom_to_arg_key(K) ++ "=" ++ term_to_arg_value(V)];
+entry_to_args(Flag) ->
+    [atom_to_arg_key(Flag)].
+
 -spec term_to_arg_value(term()) -> string().
 term_to_arg_value(X) ->
     lists:flatten(io_lib:format("~p", [X])).
@@ -6,6 +6,17 @@ pub fn generate_error_message<P: AsRef<Path>>(
     path: Option<P>,
     position: Position,
     reason: &str,
+) -> String {
+    generate_error_message_with_notes(text, path, position, reason, &[])
+}
+
+/// Same as [generate_error_message] except that `notes` are put after the message (e.g., `= note: ...`).
+pub fn generate_error_message_with_notes<P: AsRef<Path>>(
+    text: &str,
+    path: Option<P>,
+    position: Position,
+    reason: &str,
+    notes: &[String],
 ) -> String {
     let line = position.line();
     let column = position.column();
@@ -26,15 +37,23 @@ pub fn generate_error_message<P: AsRef<Path>>(
         line_width = line.to_string().len(),
         token_column = column
     ));
+    for note in notes {
+        m.push_str(&format!(
+            "\n{:line_width$} = note: {}",
+            "",
+            note,
+            line_width = line.to_string().len()
+        ));
+    }
     m
 }
 
 fn get_line_string(text: &str, position: Position) -> &str {
     let offset = position.offset();
-    let line_start = (&text[..offset]).rfind('\n').unwrap_or(0);
-    let line_end = (&text[offset..])
+    let line_start = text[..offset].rfind('\n').unwrap_or(0);
+    let line_end = text[offset..]
         .find('\n')
         .map(|x| x + offset)
         .unwrap_or_else(|| text.len());
-    (&text[line_start..line_end]).trim_matches(char::is_control)
+    text[line_start..line_end].trim_matches(char::is_control)
 }
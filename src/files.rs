@@ -1,30 +1,100 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-pub fn collect_default_target_files() -> anyhow::Result<Vec<PathBuf>> {
+pub use self::ignore::{ExcludeRules, IGNORE_FILE_NAME};
+
+mod ignore;
+
+/// Collects the format target files in the current directory.
+///
+/// The files matching `excludes` (relative to the current directory) or the patterns in the `.efmtignore` files
+/// in the root directory of the git repository and the current directory are excluded.
+pub fn collect_default_target_files(excludes: &[String]) -> anyhow::Result<Vec<PathBuf>> {
     let current_dir = std::env::current_dir()?;
-    if is_git_repository(&current_dir) {
-        collect_files_with_git(is_format_target)
-    } else {
-        collect_files_without_git(current_dir, is_format_target)
+    collect_target_files_in(&current_dir, excludes)
+}
+
+/// Replaces the directories in `paths` with the format target files in them.
+///
+/// The files are collected in the same way as [collect_default_target_files],
+/// except that the `.efmtignore` file in each directory is also loaded.
+/// Other paths (including `-` that means the standard input) are kept as they are.
+/// The resulting paths are sorted and deduplicated.
+pub fn expand_target_paths(paths: &[PathBuf], excludes: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            expanded.extend(collect_target_files_in(path, excludes)?);
+        } else {
+            expanded.push(path.clone());
+        }
+    }
+    // A file could be collected from multiple arguments (e.g., `src/` and `src/foo.erl`).
+    expanded.sort();
+    expanded.dedup();
+    Ok(expanded)
+}
+
+/// Collects the format target files in `dir`.
+///
+/// The `.efmtignore` files in the root directory of the git repository containing `dir`,
+/// the current directory and `dir` are loaded.
+fn collect_target_files_in(dir: &Path, excludes: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let current_dir = std::env::current_dir()?;
+    let mut rules = ExcludeRules::new();
+    rules.add(&current_dir, excludes)?;
+
+    let abs_dir = absolute_path(&current_dir, dir);
+    let mut ignore_file_dirs = git_root_dir(&abs_dir).into_iter().collect::<Vec<_>>();
+    for d in [current_dir.clone(), abs_dir] {
+        if !ignore_file_dirs.contains(&d) {
+            ignore_file_dirs.push(d);
+        }
+    }
+    for d in ignore_file_dirs {
+        rules.load_ignore_file(d)?;
     }
+
+    let mut files = if is_git_repository(&current_dir) {
+        collect_files_with_git(dir, &rules, is_format_target).or_else(|e| {
+            log::warn!("{}\nFalling back to walking {:?}", e, dir);
+            collect_files_without_git(dir, &rules, is_format_target)
+        })
+    } else {
+        collect_files_without_git(dir, &rules, is_format_target)
+    }?;
+    files.sort();
+    files.dedup();
+    Ok(files)
 }
 
-fn collect_files_with_git<F>(is_target: F) -> anyhow::Result<Vec<PathBuf>>
+/// Lists the files in `dir` with `git ls-files`.
+///
+/// The files in the directories skipped by [collect_files_without_git] are also skipped.
+fn collect_files_with_git<F>(
+    dir: &Path,
+    rules: &ExcludeRules,
+    is_target: F,
+) -> anyhow::Result<Vec<PathBuf>>
 where
     F: Fn(&Path) -> bool,
 {
+    let current_dir = std::env::current_dir()?;
+    let abs_dir = absolute_path(&current_dir, dir);
     let mut files = Vec::new();
     let args_list = [
         &["ls-files"][..],
         &["ls-files", "--others", "--exclude-standard"][..],
     ];
     for args in args_list {
-        let output = Command::new("git").args(args).output()?;
+        let output = Command::new("git").args(args).arg("--").arg(dir).output()?;
         anyhow::ensure!(
             output.status.success(),
-            "Failed to execute `$ git {}` command.\n{}",
+            "Failed to execute `$ git {} -- {}` command.\n{}",
             Vec::from(args).join(" "),
+            dir.display(),
             String::from_utf8_lossy(&output.stderr)
         );
         for file in String::from_utf8(output.stdout)?.split('\n') {
@@ -33,7 +103,13 @@ where
             }
 
             let path = PathBuf::from(file);
-            if !is_target(&path) {
+            let abs_path = absolute_path(&current_dir, &path);
+            let is_in_skipped_dir = abs_path
+                .strip_prefix(&abs_dir)
+                .ok()
+                .and_then(|x| x.parent())
+                .is_some_and(|x| x.iter().any(is_skipped_dir));
+            if is_in_skipped_dir || !is_target(&path) || rules.is_excluded(&abs_path, false) {
                 continue;
             }
             files.push(path);
@@ -42,51 +118,146 @@ where
     Ok(files)
 }
 
-fn collect_files_without_git<P: AsRef<Path>, F>(
-    root_dir: P,
+/// Walks `root_dir` recursively.
+///
+/// Hidden directories and `_build` directories are skipped,
+/// and symbolic links to directories are followed only once to avoid loops.
+fn collect_files_without_git<F>(
+    root_dir: &Path,
+    rules: &ExcludeRules,
     is_target: F,
 ) -> anyhow::Result<Vec<PathBuf>>
 where
     F: Fn(&Path) -> bool,
 {
+    let current_dir = std::env::current_dir()?;
+    let is_excluded =
+        |path: &Path, is_dir: bool| rules.is_excluded(absolute_path(&current_dir, path), is_dir);
+
     let mut files = Vec::new();
-    let mut stack = vec![root_dir.as_ref().to_path_buf()];
+    let mut visited_dirs = HashSet::new();
+    let mut stack = vec![root_dir.to_path_buf()];
     while let Some(dir) = stack.pop() {
+        if !visited_dirs.insert(dir.canonicalize()?) {
+            log::debug!("Skipped {:?} as it has already been visited", dir);
+            continue;
+        }
         for entry in std::fs::read_dir(dir)? {
-            let entry = entry?;
-            if entry.file_type()?.is_dir() {
-                stack.push(entry.path());
-            } else if entry.file_type()?.is_file() {
-                let path = entry.path();
-                if is_target(&path) {
-                    files.push(path);
+            let path = entry?.path();
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                // A broken symbolic link.
+                continue;
+            };
+            if metadata.is_dir() {
+                let is_skipped = path.file_name().is_some_and(is_skipped_dir);
+                if !is_skipped && !is_excluded(&path, true) {
+                    stack.push(path);
                 }
+            } else if metadata.is_file() && is_target(&path) && !is_excluded(&path, false) {
+                files.push(path);
             }
         }
     }
     Ok(files)
 }
 
+fn is_skipped_dir(name: &OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|n| n.starts_with('.') || n == "_build")
+}
+
 fn is_format_target(path: &Path) -> bool {
-    path.file_name()
-        .and_then(|n| n.to_str())
-        .map_or(false, |n| {
-            n == "rebar.config"
-                || n.ends_with(".erl")
-                || n.ends_with(".hrl")
-                || n.ends_with(".app.src")
-        })
+    path.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+        n.ends_with(".erl")
+            || n.ends_with(".hrl")
+            || n.ends_with(".app.src")
+            || n.ends_with(".escript")
+            || (n.ends_with(".config") && is_erlang_config(path, n))
+            || n.ends_with(".config.src")
+            || n.ends_with(".app")
+            || n.ends_with(".appup")
+            || n.ends_with(".relup")
+    })
+}
+
+// As `*.config` is also used by non-Erlang tools, only the well-known config files and
+// the ones in `config/` directories are targeted.
+fn is_erlang_config(path: &Path, file_name: &str) -> bool {
+    matches!(
+        file_name,
+        "rebar.config" | "sys.config" | "relx.config" | "elvis.config"
+    ) || path
+        .parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|n| n == "config")
 }
 
 fn is_git_repository<P: AsRef<Path>>(dir: P) -> bool {
+    git_root_dir(dir).is_some()
+}
+
+/// Returns the nearest ancestor directory of `dir` that contains `.git`.
+fn git_root_dir<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
     let mut dir = dir.as_ref();
     while !dir.join(".git/").exists() {
-        if let Some(parent) = dir.parent() {
-            dir = parent;
-        } else {
-            return false;
-        }
+        dir = dir.parent()?;
     }
     log::debug!("Found `.git` in {:?}", dir);
-    true
+    Some(dir.to_path_buf())
+}
+
+/// Joins `path` to `base` (an absolute path) and resolves `.` and `..` components lexically.
+fn absolute_path(base: &Path, path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                absolute.pop();
+            }
+            c => absolute.push(c),
+        }
+    }
+    absolute
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_files_without_git_works() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        for dir in ["src", ".hidden", "_build/default", "config"] {
+            std::fs::create_dir_all(root.path().join(dir))?;
+        }
+        for file in [
+            "src/b.erl",
+            "src/a.erl",
+            "src/c.txt",
+            ".hidden/d.erl",
+            "_build/default/e.erl",
+            "rebar.config",
+            "config/dev.config",
+            "src/web.config",
+        ] {
+            std::fs::write(root.path().join(file), "")?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.path(), root.path().join("src/loop"))?;
+
+        let mut files =
+            collect_files_without_git(root.path(), &ExcludeRules::new(), is_format_target)?;
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                root.path().join("config/dev.config"),
+                root.path().join("rebar.config"),
+                root.path().join("src/a.erl"),
+                root.path().join("src/b.erl")
+            ]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,117 @@
+//! Rules to exclude files from the default target files (`--exclude` options and `.efmtignore` files).
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// The name of the file that contains exclude patterns.
+pub const IGNORE_FILE_NAME: &str = ".efmtignore";
+
+/// Sets of patterns in the gitignore syntax (e.g., `_build/`, `*_parser.erl` or `!keep.erl`).
+///
+/// Each set is relative to its own root directory
+/// (i.e., the current directory for `--exclude` options and the directory containing an `.efmtignore` file).
+/// As in `.gitignore`, the last matching pattern in a set wins,
+/// and a file is excluded if any of its parent directories is excluded.
+#[derive(Debug, Default, Clone)]
+pub struct ExcludeRules {
+    matchers: Vec<Gitignore>,
+}
+
+impl ExcludeRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a set of patterns relative to `root_dir` (an absolute path).
+    ///
+    /// Empty patterns and comments (i.e., patterns starting with `#`) are ignored.
+    pub fn add<P: AsRef<Path>>(&mut self, root_dir: P, patterns: &[String]) -> anyhow::Result<()> {
+        if patterns.is_empty() {
+            return Ok(());
+        }
+        let mut builder = GitignoreBuilder::new(root_dir);
+        for pattern in patterns {
+            builder.add_line(None, pattern)?;
+        }
+        self.matchers.push(builder.build()?);
+        Ok(())
+    }
+
+    /// Adds the patterns in the `.efmtignore` file in `root_dir` (an absolute path) if exists.
+    pub fn load_ignore_file<P: AsRef<Path>>(&mut self, root_dir: P) -> anyhow::Result<()> {
+        let path = root_dir.as_ref().join(IGNORE_FILE_NAME);
+        if !path.exists() {
+            return Ok(());
+        }
+        log::debug!("Load exclude patterns from {:?}", path);
+        let mut builder = GitignoreBuilder::new(root_dir);
+        if let Some(e) = builder.add(&path) {
+            return Err(e.into());
+        }
+        self.matchers.push(builder.build()?);
+        Ok(())
+    }
+
+    /// Returns `true` if `path` (an absolute path) is excluded.
+    pub fn is_excluded<P: AsRef<Path>>(&self, path: P, is_dir: bool) -> bool {
+        let path = path.as_ref();
+        self.matchers.iter().any(|matcher| {
+            path.starts_with(matcher.path())
+                && matcher
+                    .matched_path_or_any_parents(path, is_dir)
+                    .is_ignore()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclude_rules_work() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join("efmt");
+        let mut rules = ExcludeRules::new();
+        rules.add(
+            &root,
+            &[
+                "# comment",
+                "_build/",
+                "*_parser.erl",
+                "!keep_parser.erl",
+                "/deps/*/src/",
+                "test/**/data_?.erl",
+                "[ab].erl",
+            ]
+            .map(String::from),
+        )?;
+        rules.add(root.join("apps"), &["*.hrl".to_owned()])?;
+
+        let excluded = [
+            "_build/default/lib/foo.erl",
+            "apps/foo/_build/bar.erl",
+            "src/foo_parser.erl",
+            "deps/foo/src/bar.erl",
+            "test/data_1.erl",
+            "test/foo/bar/data_2.erl",
+            "src/a.erl",
+            "apps/foo/include/bar.hrl",
+        ];
+        for path in excluded {
+            assert!(rules.is_excluded(root.join(path), false), "{path}");
+        }
+
+        let included = [
+            "_build.erl",
+            "src/keep_parser.erl",
+            "apps/deps/foo/src/bar.erl",
+            "deps/foo/include/bar.hrl",
+            "test/data_10.erl",
+            "src/c.erl",
+        ];
+        for path in included {
+            assert!(!rules.is_excluded(root.join(path), false), "{path}");
+        }
+        assert!(!rules.is_excluded("/other/src/a.erl", false));
+        Ok(())
+    }
+}
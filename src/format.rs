@@ -1,13 +1,19 @@
 use crate::span::Span;
 
 mod formatter;
+mod reflow;
+mod width;
 mod writer;
 
-pub use self::formatter::{Formatter, Indent, Newline};
+pub use self::formatter::{Formatter, Indent, ItemKind, Newline};
+pub use self::width::ColumnWidth;
 
 /// A procedural macro to derive [Format].
 pub use efmt_derive::Format;
 
+/// An attribute to record the kind of the item formatted by a hand-written method (see [Formatter::explain]).
+pub use efmt_derive::item_kind;
+
 /// This trait allows formatting an item.
 pub trait Format: Span {
     /// Formats this item.
@@ -1,8 +1,10 @@
-use crate::format::writer::{Error, RegionConfig, Result, Writer};
-use crate::format::Format;
+use crate::format::reflow::{self, CommentBlock};
+use crate::format::writer::{Error, LayoutState, RegionConfig, Result, Writer};
+use crate::format::{ColumnWidth, Format};
 use crate::items::tokens::{CommentKind, CommentToken, VisibleToken};
 use crate::parse::TokenStream;
 use crate::span::{Position, Span};
+use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct Formatter {
@@ -13,6 +15,13 @@ pub struct Formatter {
     last_token: Option<VisibleToken>,
     skip_whitespaces: bool,
     last_skipped_whitespace: Option<Item>,
+    column_width: ColumnWidth,
+    reflow_comments: bool,
+    normalize_blank_lines: bool,
+    remove_clause_body_leading_blank_lines: bool,
+    explain: bool,
+    item_kinds: Vec<ItemKind>,
+    region_count: usize,
 }
 
 impl Formatter {
@@ -25,9 +34,52 @@ impl Formatter {
             last_token: None,
             skip_whitespaces: false,
             last_skipped_whitespace: None,
+            column_width: ColumnWidth::default(),
+            reflow_comments: false,
+            normalize_blank_lines: false,
+            remove_clause_body_leading_blank_lines: false,
+            explain: false,
+            item_kinds: Vec::new(),
+            region_count: 1,
         }
     }
 
+    /// If enabled, the item kinds and layout decisions of regions are recorded (see [Formatter::explain]).
+    pub fn set_explain(&mut self, enable: bool) {
+        self.explain = enable;
+    }
+
+    /// If enabled, exactly one blank line is put between function definitions
+    /// (a spec and the following function are kept together).
+    pub fn set_normalize_blank_lines(&mut self, enable: bool) {
+        self.normalize_blank_lines = enable;
+    }
+
+    /// If enabled, blank lines at the start of clause bodies are removed.
+    pub fn set_remove_clause_body_leading_blank_lines(&mut self, enable: bool) {
+        self.remove_clause_body_leading_blank_lines = enable;
+    }
+
+    pub(crate) fn normalize_blank_lines(&self) -> bool {
+        self.normalize_blank_lines
+    }
+
+    pub(crate) fn remove_clause_body_leading_blank_lines(&self) -> bool {
+        self.remove_clause_body_leading_blank_lines
+    }
+
+    /// If enabled, overlong `%%` comment blocks are rewrapped to fit in the max columns.
+    pub fn set_reflow_comments(&mut self, enable: bool) {
+        self.reflow_comments = enable;
+    }
+
+    /// Sets how to count the width of a text in columns (the default is [ColumnWidth::Unicode]).
+    ///
+    /// This affects both the max columns check and the alignment to the current column.
+    pub fn set_column_width(&mut self, column_width: ColumnWidth) {
+        self.column_width = column_width;
+    }
+
     pub fn add_token(&mut self, token: VisibleToken) {
         let start_position = token.start_position();
         let end_position = token.end_position();
@@ -136,6 +188,16 @@ impl Formatter {
         self.item.add_newline();
     }
 
+    /// Puts a blank line before the next text (including comments) regardless of the original text.
+    pub fn add_blank_line(&mut self) {
+        self.item.add_blank_line(true);
+    }
+
+    /// Removes the blank line before the next text (including comments) even if the original text has one.
+    pub fn remove_blank_line(&mut self) {
+        self.item.add_blank_line(false);
+    }
+
     pub fn add_comment(&mut self, comment: CommentToken) {
         if comment.kind() == CommentKind::Post {
             self.add_newline();
@@ -147,15 +209,35 @@ impl Formatter {
         self.add_newline();
     }
 
+    /// Formats an item of `kind`.
+    ///
+    /// The kinds of the enclosing items are recorded in the regions created by `f` if the explain mode is enabled.
+    pub fn with_item_kind<F>(&mut self, kind: ItemKind, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        if !self.explain {
+            f(self);
+            return;
+        }
+        self.item_kinds.push(kind);
+        f(self);
+        self.item_kinds.pop();
+    }
+
     pub fn subregion<F>(&mut self, indent: Indent, newline: Newline, f: F)
     where
         F: FnOnce(&mut Self),
     {
+        let id = self.region_count;
+        self.region_count += 1;
         let prev = std::mem::replace(
             &mut self.item,
             Item::Region {
+                id,
                 indent,
                 newline,
+                kinds: self.item_kinds.clone(),
                 items: Vec::new(),
             },
         );
@@ -173,9 +255,104 @@ impl Formatter {
         &mut self.ts
     }
 
-    pub fn format(mut self, max_columns: usize) -> String {
+    /// Writes the formatted text.
+    ///
+    /// If `align_trailing_comments` is `true`, trailing comments on consecutive lines
+    /// in the same top-level item (e.g., a record definition or a run of `-define` directives) are aligned
+    /// to the same column as long as they fit in `max_columns`.
+    pub fn format(mut self, max_columns: usize, align_trailing_comments: bool) -> String {
         self.add_macros_and_comments(Position::new(usize::MAX - 1, usize::MAX, usize::MAX));
-        ItemWriter::new(&self.ts.text(), max_columns).write_to_string(&self.item)
+        let text = self.ts.text();
+        let mut writer = self.item_writer(&text, max_columns);
+        writer
+            .writer
+            .set_align_trailing_comments(align_trailing_comments);
+        writer.write_to_string(&self.item)
+    }
+
+    fn item_writer<'a>(&self, text: &'a str, max_columns: usize) -> ItemWriter<'a> {
+        let mut writer = ItemWriter::new(text, max_columns);
+        writer.writer.set_column_width(self.column_width);
+        if self.reflow_comments {
+            writer.comment_blocks = reflow::collect_comment_blocks(text, self.ts.comments());
+        }
+        writer
+    }
+
+    /// Formats the items and explains why the line `line` (1-origin) of the formatted text is laid out as it is.
+    ///
+    /// Note that `line` is a line number in the formatted text (i.e., the output of [Formatter::format]),
+    /// not in the original text.
+    /// The explanation consists of the item kinds enclosing the line and the layout decision of each region.
+    /// Note that [Formatter::set_explain] needs to be enabled before formatting the items.
+    pub fn explain(
+        mut self,
+        max_columns: usize,
+        align_trailing_comments: bool,
+        line: usize,
+    ) -> String {
+        self.add_macros_and_comments(Position::new(usize::MAX - 1, usize::MAX, usize::MAX));
+        let text = self.ts.text();
+        let mut writer = self.item_writer(&text, max_columns);
+        writer
+            .writer
+            .set_align_trailing_comments(align_trailing_comments);
+        writer.explain = true;
+        writer.write_item(&self.item).expect("bug");
+
+        // The records point to the text before `Writer::finish()`,
+        // which only changes the spaces before trailing comments (the lines are kept as they are).
+        let unfinished = writer.writer.text().to_owned();
+        let line_starts = std::iter::once(0)
+            .chain(unfinished.match_indices('\n').map(|(i, _)| i + 1))
+            .collect::<Vec<_>>();
+        let line_of = |offset: usize| line_starts.partition_point(|&x| x <= offset);
+        let mut records = writer
+            .records
+            .iter()
+            .filter(|r| {
+                let text = &unfinished[r.start..r.end];
+                let Some(first) = text.find(|c: char| !c.is_whitespace()) else {
+                    return false;
+                };
+                let last = text.trim_end().len() - 1;
+                line_of(r.start + first) <= line && line <= line_of(r.start + last)
+            })
+            .collect::<Vec<_>>();
+        records.sort_by_key(|r| r.depth);
+
+        let mut kinds = records.last().map_or_else(Vec::new, |r| r.kinds.clone());
+        kinds.dedup();
+        let formatted = writer.writer.finish();
+        let line_text = formatted
+            .lines()
+            .nth(line.wrapping_sub(1))
+            .unwrap_or_default();
+        let path = self.ts.filepath();
+        let path = path
+            .as_deref()
+            .and_then(|x| x.to_str())
+            .unwrap_or("<unknown>");
+
+        let mut m = String::new();
+        m.push_str(&format!("--> {}:{}\n", path, line));
+        m.push_str(&format!("{} | {}\n", line, line_text));
+        if records.is_empty() {
+            m.push_str("No regions were found in this line.\n");
+            return m;
+        }
+        m.push_str(&format!(
+            "{}\n",
+            kinds
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(" > ")
+        ));
+        for r in records {
+            m.push_str(&format!("- {}\n", r));
+        }
+        m
     }
 }
 
@@ -183,6 +360,23 @@ impl Formatter {
 struct ItemWriter<'a> {
     writer: Writer,
     text: &'a str,
+
+    // Failed region layout attempts keyed by the region id and the writer state at the time.
+    // As the outcome of a region only depends on the key, we can skip re-trying the same attempt.
+    failed_regions: HashMap<(usize, LayoutState), Error>,
+
+    // Comment blocks to be reflowed (empty if the reflow is disabled).
+    comment_blocks: HashMap<Position, CommentBlock>,
+
+    // The end of the comment block that was written with reflowed lines.
+    // The remaining comments in the block must be skipped.
+    // This is restored when a subregion is aborted, as the block will be written again in the retry.
+    reflowed_block_end: Option<Position>,
+
+    // The layout decisions of the written regions (recorded only if `explain` is enabled).
+    explain: bool,
+    records: Vec<RegionRecord>,
+    depth: usize,
 }
 
 impl<'a> ItemWriter<'a> {
@@ -190,6 +384,12 @@ impl<'a> ItemWriter<'a> {
         Self {
             writer: Writer::new(max_columns),
             text,
+            failed_regions: HashMap::new(),
+            comment_blocks: HashMap::new(),
+            reflowed_block_end: None,
+            explain: false,
+            records: Vec::new(),
+            depth: 0,
         }
     }
 
@@ -201,11 +401,12 @@ impl<'a> ItemWriter<'a> {
     fn write_item(&mut self, item: &Item) -> Result<()> {
         match item {
             Item::Region {
+                id,
                 indent,
                 newline,
+                kinds,
                 items,
-                ..
-            } => self.write_region(indent, newline, items)?,
+            } => self.write_region(*id, indent, newline, kinds, items)?,
             Item::Span {
                 start_position,
                 end_position,
@@ -213,6 +414,7 @@ impl<'a> ItemWriter<'a> {
             Item::Token(x) => self.write_token(x)?,
             Item::Space => self.writer.write_space()?,
             Item::Newline => self.writer.write_newline()?,
+            Item::BlankLine(x) => self.writer.set_blank_line(*x),
         }
         Ok(())
     }
@@ -227,6 +429,32 @@ impl<'a> ItemWriter<'a> {
     fn write_token(&mut self, token: &VisibleToken) -> Result<()> {
         if token.is_trailing_comment() {
             self.writer.write_trailing_comment(self.text, token)
+        } else if matches!(token, VisibleToken::Comment(_)) && !self.comment_blocks.is_empty() {
+            self.write_post_comment(token)
+        } else {
+            self.writer.write_span(self.text, token)
+        }
+    }
+
+    fn write_post_comment(&mut self, token: &VisibleToken) -> Result<()> {
+        let start = token.start_position();
+        if self.reflowed_block_end.is_some_and(|end| start < end) {
+            self.writer.skip_span(token);
+            return Ok(());
+        }
+
+        let Some(block) = self.comment_blocks.get(&start) else {
+            return self.writer.write_span(self.text, token);
+        };
+        let indent = self.writer.current_indent();
+        if let Some(lines) = block.reflow(
+            indent,
+            self.writer.max_columns(),
+            self.writer.column_width(),
+        ) {
+            let end = block.end_position();
+            self.reflowed_block_end = Some(end);
+            self.writer.write_lines(&lines, &(start, end))
         } else {
             self.writer.write_span(self.text, token)
         }
@@ -237,7 +465,53 @@ impl<'a> ItemWriter<'a> {
             .write_span(self.text, &(start_position, end_position))
     }
 
-    fn write_region(&mut self, indent: &Indent, newline: &Newline, items: &[Item]) -> Result<()> {
+    fn write_region(
+        &mut self,
+        id: usize,
+        indent: &Indent,
+        newline: &Newline,
+        kinds: &[ItemKind],
+        items: &[Item],
+    ) -> Result<()> {
+        let key = (id, self.writer.layout_state());
+        if let Some(e) = self.failed_regions.get(&key) {
+            return Err(*e);
+        }
+
+        let start = self.writer.text().len();
+        // Each top-level item (i.e., a child of the root region) is a scope of trailing comment alignment.
+        let scope = (self.depth == 1).then_some(id);
+        self.depth += 1;
+        let result = self.write_region_without_cache(indent, newline, scope, items);
+        self.depth -= 1;
+        match result {
+            Err(e) => {
+                self.failed_regions.insert(key, e);
+            }
+            Ok(decision) => {
+                if self.explain && !kinds.is_empty() {
+                    self.records.push(RegionRecord {
+                        kinds: kinds.to_vec(),
+                        indent: indent.clone(),
+                        newline: newline.clone(),
+                        decision,
+                        depth: self.depth,
+                        start,
+                        end: self.writer.text().len(),
+                    });
+                }
+            }
+        }
+        result.map(|_| ())
+    }
+
+    fn write_region_without_cache(
+        &mut self,
+        indent: &Indent,
+        newline: &Newline,
+        scope: Option<usize>,
+        items: &[Item],
+    ) -> Result<RegionDecision> {
         let indent = match indent {
             Indent::Offset(n) => self.writer.current_indent() + n,
             Indent::ParentOffset(n) => self.writer.parent_indent() + n,
@@ -293,13 +567,18 @@ impl<'a> ItemWriter<'a> {
             allow_too_long_line,
             allow_multi_line,
         };
-        let result = self.with_subregion(config, |this| {
+        let result = self.with_subregion(config, scope, |this| {
             if needs_newline {
                 this.writer.write_newline()?;
             }
             this.write_items(items)
         });
-        if result.is_err() {
+        let mut decision = RegionDecision {
+            column: indent,
+            newline: needs_newline,
+            fallback: None,
+        };
+        if let Err(e) = result {
             if !parent_allow_multi_line {
                 return Err(Error::MultiLine);
             }
@@ -317,32 +596,90 @@ impl<'a> ItemWriter<'a> {
                     allow_multi_line: true,
                 };
 
-                return self.with_subregion(config, |this| {
+                decision.fallback = Some(e);
+                decision.newline = false;
+                self.with_subregion(config, scope, |this| {
                     if needs_newline && indent < this.writer.current_column() {
+                        decision.newline = true;
                         this.writer.write_newline()?;
                     }
                     this.write_items(items)
-                });
+                })?;
+                return Ok(decision);
             }
         }
-        result
+        result.map(|_| decision)
     }
 
-    fn with_subregion<F>(&mut self, config: RegionConfig, f: F) -> Result<()>
+    fn with_subregion<F>(&mut self, config: RegionConfig, scope: Option<usize>, f: F) -> Result<()>
     where
         F: FnOnce(&mut Self) -> Result<()>,
     {
+        let records_len = self.records.len();
+        let reflowed_block_end = self.reflowed_block_end;
         self.writer.start_subregion(config);
+        if let Some(scope) = scope {
+            self.writer.set_scope(scope);
+        }
         let result = f(self);
         if result.is_ok() {
             self.writer.commit_subregion();
         } else {
             self.writer.abort_subregion();
+            self.records.truncate(records_len);
+            self.reflowed_block_end = reflowed_block_end;
         }
         result
     }
 }
 
+// How a region was laid out.
+#[derive(Debug, Clone, Copy)]
+struct RegionDecision {
+    // The indent column of the region.
+    column: usize,
+
+    // Whether a newline was put before the region.
+    newline: bool,
+
+    // The error that made the first layout attempt fail.
+    fallback: Option<Error>,
+}
+
+#[derive(Debug)]
+struct RegionRecord {
+    // The kinds of the items enclosing the region (the innermost last).
+    kinds: Vec<ItemKind>,
+    indent: Indent,
+    newline: Newline,
+    decision: RegionDecision,
+    depth: usize,
+
+    // The range of the formatted text written by the region.
+    start: usize,
+    end: usize,
+}
+
+impl std::fmt::Display for RegionRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: indent={:?} (column {}), newline={:?}",
+            self.kinds.last().expect("unreachable"),
+            self.indent,
+            self.decision.column,
+            self.newline
+        )?;
+        if let Some(e) = self.decision.fallback {
+            write!(f, ", fell back due to {:?}", e)?;
+        }
+        if self.decision.newline {
+            write!(f, ", put a newline")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 enum Item {
     Token(VisibleToken),
@@ -352,9 +689,13 @@ enum Item {
     },
     Space,
     Newline,
+    BlankLine(bool),
     Region {
+        // The identifier of the region that is unique in a [Formatter].
+        id: usize,
         indent: Indent,
         newline: Newline,
+        kinds: Vec<ItemKind>,
         items: Vec<Item>,
     },
 }
@@ -362,8 +703,10 @@ enum Item {
 impl Item {
     fn new() -> Self {
         Self::Region {
+            id: 0,
             indent: Indent::CurrentColumn,
             newline: Newline::Never,
+            kinds: Vec::new(),
             items: Vec::new(),
         }
     }
@@ -419,11 +762,19 @@ impl Item {
         }
     }
 
+    fn add_blank_line(&mut self, blank: bool) {
+        if let Self::Region { items, .. } = self {
+            items.push(Self::BlankLine(blank));
+        } else {
+            unreachable!();
+        }
+    }
+
     fn is_empty(&self) -> bool {
         if let Self::Region { items, .. } = self {
             items.iter().all(|item| match item {
                 Self::Region { items, .. } => items.is_empty(),
-                Self::Space | Self::Newline => true,
+                Self::Space | Self::Newline | Self::BlankLine(_) => true,
                 _ => false,
             })
         } else {
@@ -453,3 +804,32 @@ pub enum Newline {
     IfTooLongOrMultiLine,
     IfTooLongOrMultiLineParent,
 }
+
+/// The kind of an item (e.g., `FunDecl` or `BinaryOpCallExpr(=)`) shown in the explain mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemKind {
+    name: &'static str,
+    detail: Option<&'static str>,
+}
+
+impl ItemKind {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, detail: None }
+    }
+
+    /// Adds a detail to distinguish the item from the other items of the same kind (e.g., the operator).
+    pub const fn detail(mut self, detail: &'static str) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+impl std::fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(detail) = self.detail {
+            write!(f, "{}({})", self.name, detail)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Rewrapping of overlong comment blocks.
+use crate::format::ColumnWidth;
+use crate::items::tokens::{CommentKind, CommentToken};
+use crate::span::{Position, Span};
+use std::collections::{BTreeMap, HashMap};
+
+/// Consecutive `%%` comments that occupy whole lines.
+#[derive(Debug, Clone)]
+pub struct CommentBlock {
+    lines: Vec<String>,
+    end: Position,
+}
+
+impl CommentBlock {
+    pub fn end_position(&self) -> Position {
+        self.end
+    }
+
+    /// Rewraps the paragraphs that contain lines exceeding `max_columns`.
+    ///
+    /// Returns `None` if no lines need to be changed.
+    pub fn reflow(
+        &self,
+        indent: usize,
+        max_columns: usize,
+        column_width: ColumnWidth,
+    ) -> Option<Vec<String>> {
+        let is_too_long = |line: &str| indent + column_width.width(line) > max_columns;
+        if !self.lines.iter().any(|line| is_too_long(line)) {
+            return None;
+        }
+
+        let mut reflowed = Vec::new();
+        let mut paragraph: Vec<&str> = Vec::new();
+        for line in &self.lines {
+            match classify_line(line) {
+                Line::Verbatim => {
+                    reflow_paragraph(&paragraph, indent, max_columns, column_width, &mut reflowed);
+                    paragraph.clear();
+                    reflowed.push(line.clone());
+                }
+                Line::ParagraphStart => {
+                    reflow_paragraph(&paragraph, indent, max_columns, column_width, &mut reflowed);
+                    paragraph.clear();
+                    paragraph.push(line);
+                }
+                Line::Text => {
+                    paragraph.push(line);
+                }
+            }
+        }
+        reflow_paragraph(&paragraph, indent, max_columns, column_width, &mut reflowed);
+
+        (reflowed != self.lines).then_some(reflowed)
+    }
+}
+
+/// Collects the comment blocks that can be reflowed, keyed by the start position of the first comment.
+///
+/// Only `%%` comments are collected.
+/// Single `%` comments (which usually annotate the following code), `%%%` comments (which are usually banners)
+/// and comments between `efmt:off` and `efmt:on` are excluded.
+pub fn collect_comment_blocks(
+    text: &str,
+    comments: &BTreeMap<Position, CommentToken>,
+) -> HashMap<Position, CommentBlock> {
+    let mut blocks = HashMap::new();
+    let mut current: Option<(Position, Position, CommentBlock)> = None;
+    let mut disabled = false;
+    for comment in comments.values() {
+        let line =
+            text[comment.start_position().offset()..comment.end_position().offset()].trim_end();
+        if line.contains("efmt:off") {
+            disabled = true;
+        } else if line.contains("efmt:on") {
+            disabled = false;
+            continue;
+        }
+
+        let prefix = &line[..line.len() - line.trim_start_matches('%').len()];
+        if disabled || comment.kind() != CommentKind::Post || prefix != "%%" {
+            if let Some((start, _, block)) = current.take() {
+                blocks.insert(start, block);
+            }
+            continue;
+        }
+
+        let start = comment.start_position();
+        if let Some((_, last, block)) = &mut current {
+            if last.line() + 1 == start.line() && last.column() == start.column() {
+                block.lines.push(line.to_owned());
+                block.end = comment.end_position();
+                *last = start;
+                continue;
+            }
+        }
+        if let Some((start, _, block)) = current.take() {
+            blocks.insert(start, block);
+        }
+        let block = CommentBlock {
+            lines: vec![line.to_owned()],
+            end: comment.end_position(),
+        };
+        current = Some((start, start, block));
+    }
+    if let Some((start, _, block)) = current.take() {
+        blocks.insert(start, block);
+    }
+    blocks
+}
+
+enum Line {
+    /// A line that is kept as it is (e.g., an empty line or an indented line).
+    Verbatim,
+
+    /// A line that starts a new paragraph (e.g., an EDoc tag or a list item).
+    ParagraphStart,
+
+    /// A line that can be joined with the previous line.
+    Text,
+}
+
+fn classify_line(line: &str) -> Line {
+    let content = line.trim_start_matches('%');
+    let Some(content) = content.strip_prefix(' ') else {
+        return Line::Verbatim;
+    };
+    if content.is_empty() || content.starts_with(char::is_whitespace) {
+        Line::Verbatim
+    } else if content.starts_with(['@', '-', '*']) {
+        Line::ParagraphStart
+    } else {
+        Line::Text
+    }
+}
+
+fn reflow_paragraph(
+    paragraph: &[&str],
+    indent: usize,
+    max_columns: usize,
+    column_width: ColumnWidth,
+    reflowed: &mut Vec<String>,
+) {
+    // The lines before the first overlong line are kept as they are.
+    let first_too_long = paragraph
+        .iter()
+        .position(|line| indent + column_width.width(line) > max_columns)
+        .unwrap_or(paragraph.len());
+    reflowed.extend(
+        paragraph[..first_too_long]
+            .iter()
+            .map(|line| (*line).to_owned()),
+    );
+    let paragraph = &paragraph[first_too_long..];
+    if paragraph.is_empty() {
+        return;
+    }
+
+    let prefix = &paragraph[0][..paragraph[0].len() - paragraph[0].trim_start_matches('%').len()];
+    let mut line = String::new();
+    for word in paragraph
+        .iter()
+        .flat_map(|line| line.trim_start_matches('%').split_whitespace())
+    {
+        if line.is_empty() {
+            line = format!("{} {}", prefix, word);
+        } else if indent + column_width.width(&line) + 1 + column_width.width(word) <= max_columns {
+            line.push(' ');
+            line.push_str(word);
+        } else {
+            reflowed.push(std::mem::replace(&mut line, format!("{} {}", prefix, word)));
+        }
+    }
+    if !line.is_empty() {
+        reflowed.push(line);
+    }
+}
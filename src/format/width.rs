@@ -0,0 +1,117 @@
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr as _;
+
+/// How to count the width of a text in columns (see [crate::Options::column_width]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnWidth {
+    /// Counts the display width of each grapheme cluster.
+    ///
+    /// East Asian wide and fullwidth characters (e.g., CJK characters) take two columns,
+    /// and combining characters don't take a column by themselves.
+    #[default]
+    Unicode,
+
+    /// Counts the number of characters.
+    Chars,
+
+    /// Counts the number of bytes in UTF-8.
+    Bytes,
+}
+
+impl ColumnWidth {
+    pub const ALL: [Self; 3] = [Self::Unicode, Self::Chars, Self::Bytes];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unicode => "unicode",
+            Self::Chars => "chars",
+            Self::Bytes => "bytes",
+        }
+    }
+
+    /// Returns the width of `s` (`s` is assumed not to contain newlines).
+    pub fn width(self, s: &str) -> usize {
+        if s.is_ascii() {
+            return s.len();
+        }
+        match self {
+            Self::Unicode => s.graphemes(true).map(grapheme_width).sum(),
+            Self::Chars => s.chars().count(),
+            Self::Bytes => s.len(),
+        }
+    }
+
+    /// Calls `f` with each unit (i.e., a character or a grapheme cluster) of `s` and its width.
+    ///
+    /// If `f` returns an error, this method stops and returns the error.
+    pub(crate) fn try_for_each_unit<F, E>(self, s: &str, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(&str, usize) -> Result<(), E>,
+    {
+        if s.is_ascii() {
+            return (0..s.len()).try_for_each(|i| f(&s[i..i + 1], 1));
+        }
+        match self {
+            Self::Unicode => s.graphemes(true).try_for_each(|g| f(g, grapheme_width(g))),
+            Self::Chars | Self::Bytes => s.char_indices().try_for_each(|(i, c)| {
+                let n = c.len_utf8();
+                let width = if self == Self::Bytes { n } else { 1 };
+                f(&s[i..i + n], width)
+            }),
+        }
+    }
+}
+
+fn grapheme_width(g: &str) -> usize {
+    // ASCII control characters such as tabs take one column as in the other modes.
+    if g.is_ascii() {
+        g.len()
+    } else {
+        g.width()
+    }
+}
+
+impl FromStr for ColumnWidth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| {
+                let names = Self::ALL.map(Self::name);
+                anyhow::anyhow!(
+                    "unknown column width {:?} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for ColumnWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_works() {
+        let texts = [
+            ("foo\tbar", [7, 7, 7]),
+            ("日本語", [6, 3, 9]),
+            ("e\u{301}t\u{e9}", [3, 4, 6]),
+            ("ｱｲｳ", [3, 3, 9]),
+        ];
+        for (text, widths) in texts {
+            for (mode, width) in ColumnWidth::ALL.into_iter().zip(widths) {
+                assert_eq!(mode.width(text), width, "text={text:?}, mode={mode}");
+            }
+        }
+    }
+}
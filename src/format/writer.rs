@@ -1,6 +1,7 @@
+use crate::format::ColumnWidth;
 use crate::span::{Position, Span};
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 pub enum Error {
     #[error("max columns exceeded")]
     LineTooLong,
@@ -16,6 +17,12 @@ pub struct Writer {
     max_columns: usize,
     buf: String,
     region: RegionState,
+    align_trailing_comments: bool,
+    column_width: ColumnWidth,
+
+    // The buffer offsets where the two spaces before trailing comments start and
+    // the scopes of the lines containing the comments.
+    trailing_comments: Vec<(usize, usize)>,
 }
 
 impl Writer {
@@ -24,13 +31,117 @@ impl Writer {
             max_columns,
             buf: String::new(),
             region: RegionState::new(),
+            align_trailing_comments: false,
+            column_width: ColumnWidth::default(),
+            trailing_comments: Vec::new(),
         }
     }
 
-    pub fn finish(self) -> String {
+    pub fn set_column_width(&mut self, column_width: ColumnWidth) {
+        self.column_width = column_width;
+    }
+
+    pub fn column_width(&self) -> ColumnWidth {
+        self.column_width
+    }
+
+    pub fn set_align_trailing_comments(&mut self, enable: bool) {
+        self.align_trailing_comments = enable;
+    }
+
+    /// Sets the scope of the current region and its subregions.
+    ///
+    /// Trailing comments are aligned only among the lines that started in the same scope.
+    pub fn set_scope(&mut self, scope: usize) {
+        self.region.scope = scope;
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.align_trailing_comments {
+            self.align_trailing_comments();
+        }
         self.buf
     }
 
+    // Aligns the trailing comments on consecutive lines in the same scope (see [Writer::set_scope]) to a common column.
+    //
+    // A group of aligned lines is split before a line that would make a comment in the group exceed `max_columns`.
+    // A line whose comment exceeds `max_columns` even with the two spaces is never aligned.
+    fn align_trailing_comments(&mut self) {
+        struct Line {
+            comment_offset: usize,
+            line_start: usize,
+            line_end: usize,
+            code_width: usize,
+            comment_width: usize,
+            scope: usize,
+        }
+
+        let lines = self
+            .trailing_comments
+            .iter()
+            .map(|&(offset, scope)| {
+                let line_start = self.buf[..offset].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = self.buf[offset..]
+                    .find('\n')
+                    .map_or(self.buf.len(), |i| offset + i);
+                Line {
+                    comment_offset: offset,
+                    line_start,
+                    line_end,
+                    code_width: self.column_width.width(&self.buf[line_start..offset]),
+                    comment_width: self.column_width.width(self.buf[offset..line_end].trim()),
+                    scope,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut groups: Vec<Vec<&Line>> = Vec::new();
+        let mut group_column = 0;
+        for line in &lines {
+            let column = group_column.max(line.code_width + 2);
+            let joinable = groups
+                .last()
+                .and_then(|group| group.last())
+                .is_some_and(|last| {
+                    last.line_end + 1 == line.line_start
+                        && last.scope == line.scope
+                        && last.code_width + 2 + last.comment_width <= self.max_columns
+                });
+            let fits = |x: &Line| column + x.comment_width <= self.max_columns;
+            match groups.last_mut() {
+                Some(group) if joinable && fits(line) && group.iter().all(|x| fits(x)) => {
+                    group.push(line);
+                    group_column = column;
+                }
+                _ => {
+                    groups.push(vec![line]);
+                    group_column = line.code_width + 2;
+                }
+            }
+        }
+
+        let mut buf = String::with_capacity(self.buf.len());
+        let mut next_offset = 0;
+        for group in groups.iter().filter(|group| group.len() > 1) {
+            let column = group
+                .iter()
+                .map(|x| x.code_width)
+                .max()
+                .expect("unreachable")
+                + 2;
+            for line in group {
+                buf.push_str(&self.buf[next_offset..line.comment_offset]);
+                for _ in line.code_width..column {
+                    buf.push(' ');
+                }
+                next_offset = line.comment_offset + 2;
+            }
+        }
+        buf.push_str(&self.buf[next_offset..]);
+        self.buf = buf;
+    }
+
     fn last_whitespace_char(&self) -> Option<char> {
         let mut chars = self.buf.chars().rev();
         match (chars.next(), chars.next()) {
@@ -77,20 +188,60 @@ impl Writer {
             return Ok(());
         }
 
-        if self.region.next_position.line() + 1 < span.start_position().line() {
-            self.write("\n", false)?;
+        self.write_blank_line_if_needed(span.start_position())?;
+
+        self.write_indent_if_line_head();
+        self.write(text, false)?;
+        self.region.next_position = end;
+        Ok(())
+    }
+
+    /// Writes `lines` (e.g., reflowed comments) in place of the original text of `span`.
+    pub fn write_lines(&mut self, lines: &[String], span: &impl Span) -> Result<()> {
+        self.write_blank_line_if_needed(span.start_position())?;
+
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.write_newline()?;
+            }
+            self.write_indent_if_line_head();
+            self.write(line, false)?;
+        }
+        self.region.next_position = span.end_position();
+        Ok(())
+    }
+
+    /// Forces (`true`) or suppresses (`false`) a blank line before the next text.
+    pub fn set_blank_line(&mut self, blank: bool) {
+        self.region.blank_line = Some(blank);
+    }
+
+    fn write_blank_line_if_needed(&mut self, next_start: Position) -> Result<()> {
+        match self.region.blank_line.take() {
+            None if self.region.next_position.line() + 1 < next_start.line() => {
+                self.write("\n", false)?;
+            }
+            Some(true) if self.buf.ends_with('\n') && !self.buf.ends_with("\n\n") => {
+                self.write("\n", false)?;
+            }
+            _ => {}
         }
+        Ok(())
+    }
+
+    /// Skips the original text of `span` that has already been written by [Writer::write_lines].
+    pub fn skip_span(&mut self, span: &impl Span) {
+        self.region.next_position = span.end_position();
+    }
 
+    fn write_indent_if_line_head(&mut self) {
         if self.last_whitespace_char() == Some('\n') {
+            self.region.line_scope = self.region.scope;
             for _ in 0..self.current_indent() {
                 self.buf.push(' ');
             }
             self.region.current_column = self.current_indent();
         }
-
-        self.write(text, false)?;
-        self.region.next_position = end;
-        Ok(())
     }
 
     pub fn write_trailing_comment(&mut self, text: &str, span: &impl Span) -> Result<()> {
@@ -102,6 +253,8 @@ impl Writer {
             self.pop_last_char();
         }
 
+        self.trailing_comments
+            .push((self.buf.len(), self.region.line_scope));
         self.write("  ", true)?;
         self.write(text, true)?;
         self.region.next_position = end;
@@ -109,25 +262,30 @@ impl Writer {
     }
 
     fn write(&mut self, s: &str, skip_column_check: bool) -> Result<()> {
-        for c in s.chars() {
-            if c == '\n' {
-                if !self.is_multi_line_allowed() {
-                    return Err(Error::MultiLine);
-                }
-                self.region.current_column = 0;
-                self.region.config.allow_too_long_line = true; // Only first line is relevant.
-            } else if !skip_column_check
-                && !self.region.config.allow_too_long_line
-                && self.region.current_column >= self.max_columns
-            {
-                return Err(Error::LineTooLong);
-            }
+        self.column_width.try_for_each_unit(s, |unit, width| {
+            self.write_unit(unit, width, skip_column_check)
+        })
+    }
 
-            self.buf.push(c);
-            if c != '\n' {
-                self.region.current_column += 1;
+    // Writes a character or a grapheme cluster (e.g., `"\r\n"` or `"e\u{301}"`) that takes `width` columns.
+    fn write_unit(&mut self, unit: &str, width: usize, skip_column_check: bool) -> Result<()> {
+        if unit.ends_with('\n') {
+            if !self.is_multi_line_allowed() {
+                return Err(Error::MultiLine);
             }
+            self.region.current_column = 0;
+            self.region.config.allow_too_long_line = true; // Only first line is relevant.
+            self.buf.push_str(unit);
+            return Ok(());
+        } else if !skip_column_check
+            && !self.region.config.allow_too_long_line
+            && self.region.current_column + width > self.max_columns
+        {
+            return Err(Error::LineTooLong);
         }
+
+        self.buf.push_str(unit);
+        self.region.current_column += width;
         Ok(())
     }
 
@@ -142,9 +300,12 @@ impl Writer {
         let new = RegionState {
             config,
             next_position: self.region.next_position,
+            blank_line: self.region.blank_line,
             current_column: self.region.current_column,
             buf_start: self.buf.len(),
             popped_parent_chars: Vec::new(),
+            scope: self.region.scope,
+            line_scope: self.region.line_scope,
             parent: None,
         };
         let parent = std::mem::replace(&mut self.region, new);
@@ -155,7 +316,17 @@ impl Writer {
         let parent = *self.region.parent.take().expect("bug");
         let commited = std::mem::replace(&mut self.region, parent);
         self.region.next_position = commited.next_position;
+        self.region.blank_line = commited.blank_line;
         self.region.current_column = commited.current_column;
+        self.region.line_scope = commited.line_scope;
+        if commited.buf_start < self.region.buf_start {
+            // The committed region popped some characters written before this region started.
+            // They need to be restored if this region is aborted later.
+            self.region.buf_start = commited.buf_start;
+            self.region
+                .popped_parent_chars
+                .extend(commited.popped_parent_chars);
+        }
     }
 
     pub fn abort_subregion(&mut self) {
@@ -163,11 +334,27 @@ impl Writer {
         let aborted = std::mem::replace(&mut self.region, parent);
 
         self.buf.truncate(aborted.buf_start);
+        while self
+            .trailing_comments
+            .last()
+            .is_some_and(|&(offset, _)| offset >= aborted.buf_start)
+        {
+            self.trailing_comments.pop();
+        }
         for c in aborted.popped_parent_chars.into_iter().rev() {
             self.buf.push(c);
         }
     }
 
+    /// Returns the text written so far.
+    pub fn text(&self) -> &str {
+        &self.buf
+    }
+
+    pub fn max_columns(&self) -> usize {
+        self.max_columns
+    }
+
     pub fn current_column(&self) -> usize {
         self.region.current_column
     }
@@ -183,9 +370,34 @@ impl Writer {
     pub fn is_multi_line_allowed(&self) -> bool {
         self.region.config.allow_multi_line
     }
+
+    /// Returns a snapshot of the state that determines the outcome of subsequent writes.
+    ///
+    /// Writing the same items from two equal states always yields the same result.
+    pub fn layout_state(&self) -> LayoutState {
+        let mut chars = self.buf.chars().rev();
+        LayoutState {
+            config: self.region.config.clone(),
+            current_column: self.region.current_column,
+            outer_indent: self.region.outer_indent(),
+            next_line: self.region.next_position.line(),
+            blank_line: self.region.blank_line,
+            tail: (chars.next(), chars.next()),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LayoutState {
+    config: RegionConfig,
+    current_column: usize,
+    outer_indent: Option<usize>,
+    next_line: usize,
+    blank_line: Option<bool>,
+    tail: (Option<char>, Option<char>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RegionConfig {
     pub indent: usize,
     pub allow_too_long_line: bool,
@@ -206,9 +418,16 @@ impl RegionConfig {
 struct RegionState {
     config: RegionConfig,
     next_position: Position,
+    blank_line: Option<bool>,
     current_column: usize,
     buf_start: usize,
     popped_parent_chars: Vec<char>,
+
+    // The scope of this region and the scope of the region in which the current line started
+    // (trailing comments are only aligned among the lines in the same scope).
+    scope: usize,
+    line_scope: usize,
+
     parent: Option<Box<Self>>,
 }
 
@@ -217,13 +436,30 @@ impl RegionState {
         Self {
             config: RegionConfig::new(),
             next_position: Position::new(0, 0, 0),
+            blank_line: None,
             current_column: 0,
             buf_start: 0,
             popped_parent_chars: Vec::new(),
+            scope: 0,
+            line_scope: 0,
             parent: None,
         }
     }
 
+    // The nearest ancestor indent that differs from the current one.
+    // Together with `config.indent`, this determines every `parent_indent()` value
+    // observed by the descendant regions.
+    fn outer_indent(&self) -> Option<usize> {
+        let mut parent = self.parent.as_ref();
+        while let Some(region) = parent {
+            if region.config.indent != self.config.indent {
+                return Some(region.config.indent);
+            }
+            parent = region.parent.as_ref();
+        }
+        None
+    }
+
     fn parent_indent(&self) -> usize {
         let current = self.config.indent;
         if let Some(parent) = &self.parent {
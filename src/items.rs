@@ -29,6 +29,41 @@ pub struct ModuleOrConfig(Either<Module, Config>);
 #[derive(Debug, Clone, Span, Parse, Format)]
 pub struct Form(self::forms::Form);
 
+impl Form {
+    pub(crate) fn is_fun_decl(&self) -> bool {
+        self.0.is_fun_decl()
+    }
+
+    pub(crate) fn is_fun_spec(&self) -> bool {
+        self.0.is_fun_spec()
+    }
+
+    pub(crate) fn is_doc(&self) -> bool {
+        self.0.is_doc()
+    }
+
+    pub(crate) fn as_attr(&self) -> Option<&self::forms::Attr> {
+        self.0.as_attr()
+    }
+
+    pub(crate) fn as_include(&self) -> Option<&self::forms::IncludeDirective> {
+        self.0.as_include()
+    }
+
+    pub(crate) fn is_directive(&self) -> bool {
+        self.0.is_directive()
+    }
+
+    pub(crate) fn spec_or_type_parens(
+        &self,
+    ) -> Option<(
+        &self::symbols::OpenParenSymbol,
+        &self::symbols::CloseParenSymbol,
+    )> {
+        self.0.spec_or_type_parens()
+    }
+}
+
 /// One of [types].
 #[derive(Debug, Clone, Span, Parse, Format)]
 pub struct Type(self::types::UnionType);
@@ -1,6 +1,6 @@
 use crate::format::Format;
 use crate::items::components::Element;
-use crate::items::tokens::AtomToken;
+use crate::items::tokens::{AtomToken, LexicalToken};
 use crate::parse::{self, Parse, TokenStream};
 use crate::span::Span;
 
@@ -8,11 +8,13 @@ macro_rules! impl_parse {
     ($name:ident, $value:expr) => {
         impl Parse for $name {
             fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
-                let token: AtomToken = ts.parse()?;
-                if token.value() == $value {
-                    Ok(Self(token))
-                } else {
-                    Err(parse::Error::unexpected_token(ts, token.into()))
+                let expected = || parse::Expected::Token($value);
+                match LexicalToken::parse(ts).map_err(|e| e.expected(expected()))? {
+                    LexicalToken::Atom(token) if token.value() == $value => Ok(Self(token)),
+                    token => Err(
+                        parse::Error::unexpected_token(ts, token, ts.last_token_index())
+                            .expected(expected()),
+                    ),
                 }
             }
         }
@@ -50,3 +52,19 @@ impl_parse!(CallbackAtom, "callback");
 #[derive(Debug, Clone, Span, Format, Element)]
 pub struct RecordAtom(AtomToken);
 impl_parse!(RecordAtom, "record");
+
+#[derive(Debug, Clone, Span, Format, Element)]
+pub struct ModuleAtom(AtomToken);
+impl_parse!(ModuleAtom, "module");
+
+#[derive(Debug, Clone, Span, Format, Element)]
+pub struct FeatureAtom(AtomToken);
+impl_parse!(FeatureAtom, "feature");
+
+#[derive(Debug, Clone, Span, Format, Element)]
+pub struct DocAtom(AtomToken);
+impl_parse!(DocAtom, "doc");
+
+#[derive(Debug, Clone, Span, Format, Element)]
+pub struct ModuledocAtom(AtomToken);
+impl_parse!(ModuledocAtom, "moduledoc");
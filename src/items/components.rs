@@ -26,7 +26,11 @@ impl Span for Never {
 impl Parse for Never {
     fn parse(ts: &mut parse::TokenStream) -> parse::Result<Self> {
         let token = ts.parse()?;
-        Err(parse::Error::unexpected_token(ts, token))
+        Err(parse::Error::unexpected_token(
+            ts,
+            token,
+            ts.last_token_index(),
+        ))
     }
 }
 
@@ -89,7 +93,7 @@ impl<T> Maybe<T> {
 
 impl<T: Element> Element for Maybe<T> {
     fn is_packable(&self) -> bool {
-        self.get().map_or(true, Element::is_packable)
+        self.get().is_none_or(Element::is_packable)
     }
 }
 
@@ -110,6 +114,10 @@ impl<T> Parenthesized<T> {
     pub fn get(&self) -> &T {
         &self.item
     }
+
+    pub fn parens(&self) -> (&OpenParenSymbol, &CloseParenSymbol) {
+        (&self.open, &self.close)
+    }
 }
 
 impl<T: Format> Format for Parenthesized<T> {
@@ -287,6 +295,16 @@ pub struct ListLike<T: Element, D = CommaDelimiter> {
     close: CloseSquareSymbol,
 }
 
+impl<T: Element, D> ListLike<T, D> {
+    pub fn items(&self) -> &[T] {
+        self.items.0.items()
+    }
+
+    pub fn delimiters(&self) -> &[D] {
+        self.items.0.delimiters()
+    }
+}
+
 #[derive(Debug, Clone, Span, Parse, Format)]
 pub struct TupleLike<T: Element> {
     open: OpenBraceSymbol,
@@ -409,6 +427,15 @@ pub struct WithArrow<T> {
     arrow: RightArrowSymbol,
 }
 
+impl<T: Parse> ResumeParse<T> for WithArrow<T> {
+    fn resume_parse(ts: &mut parse::TokenStream, item: T) -> parse::Result<Self> {
+        Ok(Self {
+            item,
+            arrow: ts.parse()?,
+        })
+    }
+}
+
 impl<T: Format> Format for WithArrow<T> {
     fn format(&self, fmt: &mut Formatter) {
         self.item.format(fmt);
@@ -424,6 +451,15 @@ pub struct WithGuard<T, U, D = GuardDelimiter> {
     guard: Maybe<Guard<U, D>>,
 }
 
+impl<T: Parse, U: Parse, D: Parse> ResumeParse<T> for WithGuard<T, U, D> {
+    fn resume_parse(ts: &mut parse::TokenStream, item: T) -> parse::Result<Self> {
+        Ok(Self {
+            item,
+            guard: ts.parse()?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Span, Parse)]
 struct Guard<T, D> {
     when: WhenKeyword,
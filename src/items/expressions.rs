@@ -70,15 +70,30 @@ impl Parse for BaseExpr {
                         ts.parse().map(Self::RecordConstructOrIndex)
                     }
                 }
-                _ => ts.parse().map(Self::UnaryOpCall),
+                Symbol::Plus | Symbol::Hyphen => ts.parse().map(Self::UnaryOpCall),
+                _ => Err(
+                    parse::Error::unexpected_token(ts, token.into(), ts.last_token_index())
+                        .expected(parse::Expected::Kind("expression")),
+                ),
             },
             Some(LexicalToken::Keyword(token)) => match token.value() {
                 Keyword::Fun => ts.parse().map(Self::Function),
                 Keyword::Bnot | Keyword::Not => ts.parse().map(Self::UnaryOpCall),
-                _ => ts.parse().map(Self::Block),
+                Keyword::Case
+                | Keyword::If
+                | Keyword::Receive
+                | Keyword::Begin
+                | Keyword::Try
+                | Keyword::Catch => ts.parse().map(Self::Block),
+                _ => Err(
+                    parse::Error::unexpected_token(ts, token.into(), ts.last_token_index())
+                        .expected(parse::Expected::Kind("expression")),
+                ),
             },
             Some(_) => ts.parse().map(Self::Literal),
-            None => Err(parse::Error::unexpected_eof(ts)),
+            None => {
+                Err(parse::Error::unexpected_eof(ts).expected(parse::Expected::Kind("expression")))
+            }
         }?;
 
         let mut expr = expr;
@@ -154,6 +169,32 @@ impl FullExpr {
     pub fn is_integer_token(&self) -> bool {
         matches!(self, Self::Base(BaseExpr::Literal(LiteralExpr::Integer(_))))
     }
+
+    pub fn as_atom_token(&self) -> Option<&AtomToken> {
+        if let Self::Base(BaseExpr::Literal(LiteralExpr::Atom(x))) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the name and the arity if this is an expression like `foo/1`.
+    pub(crate) fn as_name_and_arity(&self) -> Option<(&AtomToken, &Expr)> {
+        if let Self::BinaryOpCall(x) = self {
+            x.name_and_arity()
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn as_proper_list_items(&self) -> Option<&[Expr]> {
+        if let Self::Base(BaseExpr::List(x)) = self {
+            if let ListExpr::Construct(x) = &**x {
+                return x.proper_list_items();
+            }
+        }
+        None
+    }
 }
 
 impl Element for FullExpr {
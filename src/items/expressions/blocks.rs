@@ -1,4 +1,4 @@
-use crate::format::{Format, Formatter, Indent, Newline};
+use crate::format::{item_kind, Format, Formatter, Indent, Newline};
 use crate::items::components::{Clauses, Either, Maybe, NonEmptyItems, WithArrow, WithGuard};
 use crate::items::expressions::components::Body;
 use crate::items::keywords::{
@@ -36,6 +36,7 @@ pub struct CaseExpr {
 }
 
 impl Format for CaseExpr {
+    #[item_kind("CaseExpr")]
     fn format(&self, fmt: &mut Formatter) {
         self.case.format(fmt);
         fmt.add_space();
@@ -148,6 +149,7 @@ pub struct TryExpr {
 }
 
 impl Format for TryExpr {
+    #[item_kind("TryExpr")]
     fn format(&self, fmt: &mut Formatter) {
         self.r#try.format(fmt);
         self.body.format(fmt);
@@ -196,6 +198,7 @@ pub struct CatchExpr {
 }
 
 impl Format for CatchExpr {
+    #[item_kind("CatchExpr")]
     fn format(&self, fmt: &mut Formatter) {
         self.catch.format(fmt);
         fmt.add_space();
@@ -1,8 +1,9 @@
-use crate::format::{Format, Formatter};
+use crate::format::{item_kind, Format, Formatter};
 use crate::items::components::{Args, BinaryOpLike, Maybe, UnaryOpLike};
 use crate::items::expressions::components::{BinaryOp, UnaryOp};
 use crate::items::expressions::BaseExpr;
 use crate::items::symbols::ColonSymbol;
+use crate::items::tokens::AtomToken;
 use crate::items::Expr;
 use crate::parse::{self, Parse, ResumeParse};
 use crate::span::Span;
@@ -66,9 +67,18 @@ impl BinaryOpCallExpr {
             && matches!(self.0.op, BinaryOp::FloatDiv(_))
             && self.0.right.get().is_integer_token()
     }
+
+    pub(crate) fn name_and_arity(&self) -> Option<(&AtomToken, &Expr)> {
+        if self.is_name_and_arity() {
+            Some((self.0.left.get().as_atom_token()?, &self.0.right))
+        } else {
+            None
+        }
+    }
 }
 
 impl Format for BinaryOpCallExpr {
+    #[item_kind("BinaryOpCallExpr", detail = self.0.op.as_str())]
     fn format(&self, fmt: &mut Formatter) {
         if self.is_name_and_arity() {
             // A workaround for some attributes such as `-export` and `-import`.
@@ -1,32 +1,83 @@
-use crate::format::{Format, Formatter, Indent, Newline};
+use crate::format::{item_kind, Format, Formatter, Indent, Newline};
 use crate::items::components::{
-    BinaryOpLike, BinaryOpStyle, Either, NonEmptyItems, Params, WithArrow, WithGuard,
+    BinaryOpLike, BinaryOpStyle, Either, NonEmptyItems, Null, Params, WithArrow, WithGuard,
 };
 use crate::items::keywords;
 use crate::items::symbols::{
     self, CommaSymbol, DoubleLeftArrowSymbol, DoubleVerticalBarSymbol, LeftArrowSymbol,
 };
-use crate::items::tokens::LexicalToken;
+use crate::items::tokens::{AtomToken, LexicalToken, VariableToken};
 use crate::items::Expr;
-use crate::parse::{self, Parse};
+use crate::parse::{self, Parse, TokenStream};
 use crate::span::Span;
 use erl_tokenize::values::{Keyword, Symbol};
 
-#[derive(Debug, Clone, Span, Parse, Format)]
+#[derive(Debug, Clone, Span)]
 pub(crate) struct FunctionClause<Name> {
     name: Name,
     params: WithArrow<WithGuard<Params<Expr>, Expr>>,
     body: Body,
 }
 
+impl<Name: FunctionClauseName> Parse for FunctionClause<Name> {
+    fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
+        let name: Name = ts.parse()?;
+        let params: Params<Expr> = ts.parse()?;
+        if let Some(function_name) = name.function_name() {
+            // `?FUNCTION_NAME` and `?FUNCTION_ARITY` in the guard and body refer to this function.
+            ts.set_current_function(function_name);
+            ts.set_current_function_arity(params.get().len());
+        }
+        let params = ts.resume_parse(params)?;
+        Ok(Self {
+            name,
+            params: ts.resume_parse(params)?,
+            body: ts.parse()?,
+        })
+    }
+}
+
+/// The name of a [FunctionClause] (e.g., [AtomToken] for function declarations).
+pub(crate) trait FunctionClauseName: Parse {
+    /// Returns the function name if the clause is a part of a function declaration.
+    fn function_name(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl FunctionClauseName for AtomToken {
+    fn function_name(&self) -> Option<&str> {
+        Some(self.value())
+    }
+}
+
+impl FunctionClauseName for VariableToken {}
+
+impl FunctionClauseName for Null {}
+
+impl<Name: Format> Format for FunctionClause<Name> {
+    #[item_kind("FunctionClause")]
+    fn format(&self, fmt: &mut Formatter) {
+        self.name.format(fmt);
+        self.params.format(fmt);
+        self.body.format(fmt);
+    }
+}
+
 impl<Name: Format> FunctionClause<Name> {
+    #[item_kind("FunctionClause")]
     pub fn format_maybe_one_line_body(&self, fmt: &mut Formatter) {
         self.name.format(fmt);
         self.params.format(fmt);
         fmt.subregion(
             Indent::Offset(4),
             Newline::IfTooLongOrMultiLineParent,
-            |fmt| self.body.exprs.format(fmt),
+            |fmt| {
+                if fmt.remove_clause_body_leading_blank_lines() {
+                    fmt.remove_blank_line();
+                }
+                self.body.exprs.format(fmt)
+            },
         );
     }
 
@@ -48,8 +99,12 @@ impl Body {
 }
 
 impl Format for Body {
+    #[item_kind("Body")]
     fn format(&self, fmt: &mut Formatter) {
         fmt.subregion(Indent::Offset(4), Newline::Always, |fmt| {
+            if fmt.remove_clause_body_leading_blank_lines() {
+                fmt.remove_blank_line();
+            }
             self.exprs.format_multi_line(fmt)
         });
     }
@@ -170,7 +225,11 @@ impl Parse for BinaryOp {
                 Symbol::Greater => ts.parse().map(Self::Greater),
                 Symbol::GreaterEq => ts.parse().map(Self::GreaterEq),
                 Symbol::Not => ts.parse().map(Self::Send),
-                _ => Err(parse::Error::unexpected_token(ts, token.into())),
+                _ => Err(parse::Error::unexpected_token(
+                    ts,
+                    token.into(),
+                    ts.last_token_index(),
+                )),
             },
             Some(LexicalToken::Keyword(token)) => match token.value() {
                 Keyword::Div => ts.parse().map(Self::IntDiv),
@@ -185,14 +244,57 @@ impl Parse for BinaryOp {
                 Keyword::And => ts.parse().map(Self::And),
                 Keyword::Andalso => ts.parse().map(Self::Andalso),
                 Keyword::Orelse => ts.parse().map(Self::Orelse),
-                _ => Err(parse::Error::unexpected_token(ts, token.into())),
+                _ => Err(parse::Error::unexpected_token(
+                    ts,
+                    token.into(),
+                    ts.last_token_index(),
+                )),
             },
-            Some(token) => Err(parse::Error::unexpected_token(ts, token)),
+            Some(token) => Err(parse::Error::unexpected_token(
+                ts,
+                token,
+                ts.last_token_index(),
+            )),
             None => Err(parse::Error::unexpected_eof(ts)),
         }
     }
 }
 
+impl BinaryOp {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plus(_) => "+",
+            Self::Minus(_) => "-",
+            Self::Mul(_) => "*",
+            Self::FloatDiv(_) => "/",
+            Self::PlusPlus(_) => "++",
+            Self::MinusMinus(_) => "--",
+            Self::Match(_) => "=",
+            Self::Eq(_) => "==",
+            Self::ExactEq(_) => "=:=",
+            Self::NotEq(_) => "/=",
+            Self::ExactNotEq(_) => "=/=",
+            Self::Less(_) => "<",
+            Self::LessEq(_) => "=<",
+            Self::Greater(_) => ">",
+            Self::GreaterEq(_) => ">=",
+            Self::Send(_) => "!",
+            Self::IntDiv(_) => "div",
+            Self::Rem(_) => "rem",
+            Self::Bor(_) => "bor",
+            Self::Bxor(_) => "bxor",
+            Self::Band(_) => "band",
+            Self::Bsl(_) => "bsl",
+            Self::Bsr(_) => "bsr",
+            Self::Or(_) => "or",
+            Self::Xor(_) => "xor",
+            Self::And(_) => "and",
+            Self::Andalso(_) => "andalso",
+            Self::Orelse(_) => "orelse",
+        }
+    }
+}
+
 impl BinaryOpStyle for BinaryOp {
     fn indent(&self) -> Indent {
         if matches!(self, Self::Match(_)) {
@@ -1,13 +1,13 @@
 use crate::format::{Format, Formatter, Indent, Newline};
 use crate::items::components::{Clauses, Maybe, Null};
-use crate::items::expressions::components::FunctionClause;
+use crate::items::expressions::components::{FunctionClause, FunctionClauseName};
 use crate::items::expressions::BaseExpr;
 use crate::items::keywords::{EndKeyword, FunKeyword};
 use crate::items::symbols::{ColonSymbol, SlashSymbol};
 use crate::items::tokens::VariableToken;
 #[cfg(doc)]
 use crate::items::Expr;
-use crate::parse::Parse;
+use crate::parse::{self, Parse, TokenStream};
 use crate::span::Span;
 
 #[derive(Debug, Clone, Span, Parse, Format)]
@@ -63,12 +63,21 @@ impl Format for Fun {
     }
 }
 
-#[derive(Debug, Clone, Span, Parse)]
+#[derive(Debug, Clone, Span)]
 struct FunctionClausesAndEnd<Name> {
     clauses: Clauses<FunctionClause<Name>>,
     end: EndKeyword,
 }
 
+impl<Name: FunctionClauseName> Parse for FunctionClausesAndEnd<Name> {
+    fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
+        Ok(Self {
+            clauses: ts.parse()?,
+            end: ts.parse()?,
+        })
+    }
+}
+
 impl<Name: Format> Format for FunctionClausesAndEnd<Name> {
     fn format(&self, fmt: &mut Formatter) {
         if self.clauses.items().len() == 1 && self.clauses.items()[0].body().exprs().len() == 1 {
@@ -19,6 +19,17 @@ pub enum ListExpr {
 #[derive(Debug, Clone, Span, Parse, Format)]
 pub struct ListConstructExpr(ListLike<Expr, ListItemDelimiter>);
 
+impl ListConstructExpr {
+    /// Returns the items of this list if it is a proper list (i.e., it has no `|` tail).
+    pub(crate) fn proper_list_items(&self) -> Option<&[Expr]> {
+        self.0
+            .delimiters()
+            .iter()
+            .all(|x| matches!(x, ListItemDelimiter::Comma(_)))
+            .then(|| self.0.items())
+    }
+}
+
 #[derive(Debug, Clone, Span, Parse)]
 enum ListItemDelimiter {
     Comma(CommaSymbol),
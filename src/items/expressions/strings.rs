@@ -1,10 +1,12 @@
-use crate::format::{Format, Formatter, Indent, Newline};
+use crate::format::{item_kind, Format, Formatter, Indent, Newline};
 use crate::items::components::Element;
 use crate::items::tokens::StringToken;
 use crate::parse::{self, Parse};
 use crate::span::{Position, Span};
 
 /// [StringToken]+
+///
+/// Triple-quoted strings and sigils are not concatenated with adjacent strings.
 #[derive(Debug, Clone)]
 pub struct StringExpr(Vec<StringToken>);
 
@@ -20,15 +22,21 @@ impl Span for StringExpr {
 
 impl Parse for StringExpr {
     fn parse(ts: &mut parse::TokenStream) -> parse::Result<Self> {
-        let mut items = vec![ts.parse()?];
-        while let Ok(item) = ts.parse() {
-            items.push(item);
+        let first: StringToken = ts.parse()?;
+        if first.is_verbatim() {
+            return Ok(Self(vec![first]));
+        }
+
+        let mut items = vec![first];
+        while ts.peek::<StringToken>().is_some_and(|x| !x.is_verbatim()) {
+            items.push(ts.parse()?);
         }
         Ok(Self(items))
     }
 }
 
 impl Format for StringExpr {
+    #[item_kind("StringExpr")]
     fn format(&self, fmt: &mut Formatter) {
         fmt.subregion(Indent::CurrentColumn, Newline::Never, |fmt| {
             for (i, item) in self.0.iter().enumerate() {
@@ -69,4 +77,49 @@ mod tests {
             crate::assert_format!(text, Expr);
         }
     }
+
+    #[test]
+    fn verbatim_string_works() {
+        let texts = [
+            r#"~"foo\"""#,
+            r#"~S[foo\]"#,
+            r#"~b{foo}"#,
+            indoc::indoc! {r#"
+            """
+              foo "bar"
+              """"#},
+            indoc::indoc! {r#"
+            %---10---|%---20---|
+            [~"foo",
+             ~B<bar>,
+             "baz"
+             "qux"]"#},
+            indoc::indoc! {r#"
+            %---10---|%---20---|
+            foo(~"bar",
+                """
+                  baz
+                """)"#},
+        ];
+        for text in texts {
+            crate::assert_format!(text, Expr);
+        }
+    }
+
+    #[test]
+    fn verbatim_string_spacing_works() {
+        let text = r#"case~"a" of~"a"->~b"a"u;"b"->{~"b",<<~"c"/binary>>}end"#;
+        let expected = indoc::indoc! {r#"
+        case ~"a" of
+            ~"a" ->
+                ~b"a"u;
+            "b" ->
+                {~"b", <<~"c"/binary>>}
+        end"#};
+        let formatted = crate::Options::new()
+            .max_columns(40)
+            .format_text::<Expr>(text)
+            .unwrap();
+        similar_asserts::assert_str_eq!(formatted, expected);
+    }
 }
@@ -1,8 +1,8 @@
 //! Erlang top-level components such as attributes, directives or declarations.
-use crate::format::{Format, Formatter, Indent, Newline};
+use crate::format::{item_kind, Format, Formatter, Indent, Newline};
 use crate::items::atoms::{
-    CallbackAtom, DefineAtom, IncludeAtom, IncludeLibAtom, OpaqueAtom, RecordAtom, SpecAtom,
-    TypeAtom,
+    CallbackAtom, DefineAtom, DocAtom, FeatureAtom, IncludeAtom, IncludeLibAtom, ModuleAtom,
+    ModuledocAtom, OpaqueAtom, RecordAtom, SpecAtom, TypeAtom,
 };
 use crate::items::components::{
     Clauses, CommaDelimiter, Either, Element, Maybe, Never, NonEmptyItems, Null, Params,
@@ -18,8 +18,8 @@ use crate::items::symbols::{
 use crate::items::tokens::{AtomToken, LexicalToken, StringToken, VariableToken};
 use crate::items::Expr;
 use crate::items::Type;
-use crate::parse::Parse;
-use crate::span::Span;
+use crate::parse::{self, Parse, TokenStream};
+use crate::span::{Position, Span};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Span, Parse, Format)]
@@ -30,7 +30,53 @@ pub(super) enum Form {
     FunDecl(FunDecl),
     TypeDecl(TypeDecl),
     RecordDecl(RecordDecl),
+    DocAttr(DocAttr),
     Attr(Attr),
+    Broken(BrokenForm),
+}
+
+impl Form {
+    pub(super) fn is_fun_decl(&self) -> bool {
+        matches!(self, Self::FunDecl(_))
+    }
+
+    pub(super) fn is_fun_spec(&self) -> bool {
+        matches!(self, Self::FunSpec(_))
+    }
+
+    /// Returns `true` if this is a `-doc` attribute (that documents the following form).
+    pub(super) fn is_doc(&self) -> bool {
+        matches!(self, Self::DocAttr(x) if x.is_doc())
+    }
+
+    pub(super) fn as_attr(&self) -> Option<&Attr> {
+        if let Self::Attr(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn as_include(&self) -> Option<&IncludeDirective> {
+        if let Self::Include(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn is_directive(&self) -> bool {
+        matches!(self, Self::Define(_) | Self::Include(_))
+    }
+
+    /// Returns the parentheses enclosing the value of `-spec`, `-callback`, `-type` or `-opaque` (e.g., `-spec(foo() -> ok).`).
+    pub(super) fn spec_or_type_parens(&self) -> Option<(&OpenParenSymbol, &CloseParenSymbol)> {
+        match self {
+            Self::FunSpec(x) => x.0.value_parens(),
+            Self::TypeDecl(x) => x.0.value_parens(),
+            _ => None,
+        }
+    }
 }
 
 /// `-` `record` `(` `$NAME` `,` `{` `$FIELD`* `}` `)` `.`
@@ -48,6 +94,7 @@ struct RecordDeclValue {
 }
 
 impl Format for RecordDeclValue {
+    #[item_kind("RecordDeclValue")]
     fn format(&self, fmt: &mut Formatter) {
         fmt.subregion(Indent::CurrentColumn, Newline::Never, |fmt| {
             self.name.format(fmt);
@@ -68,6 +115,7 @@ struct RecordField {
 }
 
 impl Format for RecordField {
+    #[item_kind("RecordField")]
     fn format(&self, fmt: &mut Formatter) {
         self.name.format(fmt);
         if let Some((x, y)) = self.default.get() {
@@ -106,6 +154,7 @@ struct TypeDeclItem {
 }
 
 impl Format for TypeDeclItem {
+    #[item_kind("TypeDeclItem")]
     fn format(&self, fmt: &mut Formatter) {
         fmt.subregion(Indent::CurrentColumn, Newline::Never, |fmt| {
             self.name.format(fmt);
@@ -132,14 +181,28 @@ pub struct FunSpec(AttrLike<FunSpecName, FunSpecItem>);
 
 type FunSpecName = Either<SpecAtom, CallbackAtom>;
 
-#[derive(Debug, Clone, Span, Parse)]
+#[derive(Debug, Clone, Span)]
 struct FunSpecItem {
     module_name: Maybe<(AtomToken, ColonSymbol)>,
     function_name: AtomToken,
     clauses: Clauses<SpecClause>,
 }
 
+impl Parse for FunSpecItem {
+    fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
+        let module_name = ts.parse()?;
+        let function_name: AtomToken = ts.parse()?;
+        ts.set_current_function(function_name.value());
+        Ok(Self {
+            module_name,
+            function_name,
+            clauses: ts.parse()?,
+        })
+    }
+}
+
 impl Format for FunSpecItem {
+    #[item_kind("FunSpecItem")]
     fn format(&self, fmt: &mut Formatter) {
         fmt.subregion(Indent::CurrentColumn, Newline::Never, |fmt| {
             self.module_name.format(fmt);
@@ -149,13 +212,26 @@ impl Format for FunSpecItem {
     }
 }
 
-#[derive(Debug, Clone, Span, Parse)]
+#[derive(Debug, Clone, Span)]
 struct SpecClause {
     params: WithArrow<Params<Type>>,
     r#return: WithGuard<Type, Type, CommaDelimiter>,
 }
 
+impl Parse for SpecClause {
+    fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
+        let params: Params<Type> = ts.parse()?;
+        // `?FUNCTION_ARITY` in the return type refers to the arity of this clause.
+        ts.set_current_function_arity(params.get().len());
+        Ok(Self {
+            params: ts.resume_parse(params)?,
+            r#return: ts.parse()?,
+        })
+    }
+}
+
 impl Format for SpecClause {
+    #[item_kind("SpecClause")]
     fn format(&self, fmt: &mut Formatter) {
         self.params.format(fmt);
         fmt.subregion(
@@ -178,6 +254,64 @@ pub struct FunDecl {
     dot: DotSymbol,
 }
 
+/// `-` (`doc` | `moduledoc`) `$VALUE` `.`
+///
+/// - $VALUE: [Expr] (e.g., a triple-quoted string, a metadata map or `false`)
+///
+/// Strings are kept verbatim and maps are formatted in the same way as map expressions.
+#[derive(Debug, Clone, Span, Parse, Format)]
+pub struct DocAttr(AttrLike<Either<DocAtom, ModuledocAtom>, Expr>);
+
+impl DocAttr {
+    pub fn is_doc(&self) -> bool {
+        matches!(self.0.name, Either::A(_))
+    }
+}
+
+/// A form that could not be parsed.
+///
+/// This item is only parsed if [TokenStream::set_recover_errors] or [TokenStream::set_allow_partial] is enabled,
+/// and the text of the form is kept as it is.
+#[derive(Debug, Clone)]
+pub struct BrokenForm {
+    start: Position,
+    end: Position,
+}
+
+impl Span for BrokenForm {
+    fn start_position(&self) -> Position {
+        self.start
+    }
+
+    fn end_position(&self) -> Position {
+        self.end
+    }
+}
+
+impl Parse for BrokenForm {
+    fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
+        let Some(error) = ts.take_last_error() else {
+            // Every other kind of form records its parse error, but the next token is reported just in case.
+            return Err(match ts.next() {
+                Some(Ok(token)) => parse::Error::unexpected_token(ts, token, ts.last_token_index()),
+                Some(Err(e)) => e,
+                None => parse::Error::unexpected_eof(ts),
+            });
+        };
+        if !ts.recover_errors() {
+            return Err(error);
+        }
+        let (start, end) = ts.skip_broken_form(error);
+        Ok(Self { start, end })
+    }
+}
+
+impl Format for BrokenForm {
+    fn format(&self, fmt: &mut Formatter) {
+        fmt.add_span(self);
+    }
+}
+
 /// `-` `$NAME` `$ARGS`? `.`
 ///
 /// - $NAME: [AtomToken] | `if`
@@ -189,6 +323,26 @@ pub struct Attr(AttrLike<AttrName, AttrValue, Null>);
 type AttrName = Either<AtomToken, IfKeyword>;
 type AttrValue = NonEmptyItems<Expr>;
 
+impl Attr {
+    pub fn name(&self) -> &str {
+        match &self.0.name {
+            Either::A(x) => x.value(),
+            Either::B(_) => "if",
+        }
+    }
+
+    /// Returns the items of the value if it is a single proper list enclosed in parentheses (e.g., `-export([foo/0]).`).
+    pub(crate) fn list_value(&self) -> Option<&[Expr]> {
+        let Either::A(value) = &self.0.value else {
+            return None;
+        };
+        match value.get().items() {
+            [item] => item.get().as_proper_list_items(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Span, Parse)]
 struct AttrLike<Name, Value, Empty = Never> {
     hyphen: HyphenSymbol,
@@ -197,6 +351,16 @@ struct AttrLike<Name, Value, Empty = Never> {
     dot: DotSymbol,
 }
 
+impl<Name, Value, Empty> AttrLike<Name, Value, Empty> {
+    fn value_parens(&self) -> Option<(&OpenParenSymbol, &CloseParenSymbol)> {
+        if let Either::A(x) = &self.value {
+            Some(x.parens())
+        } else {
+            None
+        }
+    }
+}
+
 impl<Name: Format, Value: Format, Empty: Format> Format for AttrLike<Name, Value, Empty> {
     fn format(&self, fmt: &mut Formatter) {
         self.hyphen.format(fmt);
@@ -242,6 +406,7 @@ impl DefineDirective {
 }
 
 impl Format for DefineDirective {
+    #[item_kind("DefineDirective")]
     fn format(&self, fmt: &mut Formatter) {
         self.hyphen.format(fmt);
         self.define.format(fmt);
@@ -260,6 +425,142 @@ impl Format for DefineDirective {
     }
 }
 
+/// `-` `module` `(` `$NAME` `)` `.`
+///
+/// - $NAME: [AtomToken]
+///
+/// Note that this item is only used by [TokenStream] to determine the value of `?MODULE`
+/// (the attribute is formatted as an [Attr]).
+#[derive(Debug, Clone, Span, Parse, Format)]
+pub(crate) struct ModuleAttr {
+    hyphen: HyphenSymbol,
+    module: ModuleAtom,
+    open: OpenParenSymbol,
+    name: AtomToken,
+    close: CloseParenSymbol,
+    dot: DotSymbol,
+}
+
+impl ModuleAttr {
+    pub fn module_name(&self) -> &str {
+        self.name.value()
+    }
+}
+
+/// `-` `feature` `(` `$NAME` `,` `$ACTION` `)` `.`
+///
+/// - $NAME: [AtomToken]
+/// - $ACTION: `enable` | `disable`
+///
+/// Note that this item is only used by [TokenStream] to determine the value of `?FEATURE_ENABLED(..)`
+/// (the attribute is formatted as an [Attr]).
+#[derive(Debug, Clone, Span, Parse, Format)]
+pub(crate) struct FeatureAttr {
+    hyphen: HyphenSymbol,
+    feature: FeatureAtom,
+    open: OpenParenSymbol,
+    name: AtomToken,
+    comma: CommaSymbol,
+    action: AtomToken,
+    close: CloseParenSymbol,
+    dot: DotSymbol,
+}
+
+impl FeatureAttr {
+    pub fn feature_name(&self) -> &str {
+        self.name.value()
+    }
+
+    pub fn is_enable(&self) -> bool {
+        self.action.value() == "enable"
+    }
+}
+
+/// `-` `$NAME` (`(` `$ARG`* `)`)? `.`
+///
+/// - $NAME: `if` | `elif` | `ifdef` | `ifndef` | `else` | `endif`
+/// - $ARG: [LexicalToken]
+///
+/// Note that this item is only used by [TokenStream] to evaluate conditional compilation
+/// (the directive is formatted as an [Attr]).
+#[derive(Debug, Clone)]
+pub(crate) struct ConditionalDirective {
+    hyphen: HyphenSymbol,
+    kind: ConditionalKind,
+    args: Vec<LexicalToken>,
+    dot: DotSymbol,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConditionalKind {
+    If,
+    Elif,
+    Ifdef,
+    Ifndef,
+    Else,
+    Endif,
+}
+
+impl ConditionalDirective {
+    pub fn kind(&self) -> ConditionalKind {
+        self.kind
+    }
+
+    /// Returns the tokens between the parentheses (the macros in them have been expanded).
+    pub fn args(&self) -> &[LexicalToken] {
+        &self.args
+    }
+}
+
+impl Span for ConditionalDirective {
+    fn start_position(&self) -> Position {
+        self.hyphen.start_position()
+    }
+
+    fn end_position(&self) -> Position {
+        self.dot.end_position()
+    }
+}
+
+impl Parse for ConditionalDirective {
+    fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
+        let hyphen = ts.parse()?;
+        let name: Either<IfKeyword, AtomToken> = ts.parse()?;
+        let kind = match name {
+            Either::A(_) => ConditionalKind::If,
+            Either::B(name) => match name.value() {
+                "elif" => ConditionalKind::Elif,
+                "ifdef" => ConditionalKind::Ifdef,
+                "ifndef" => ConditionalKind::Ifndef,
+                "else" => ConditionalKind::Else,
+                "endif" => ConditionalKind::Endif,
+                _ => {
+                    return Err(parse::Error::unexpected_token(
+                        ts,
+                        name.into(),
+                        ts.last_token_index(),
+                    ))
+                }
+            },
+        };
+
+        let mut args = Vec::new();
+        if !matches!(kind, ConditionalKind::Else | ConditionalKind::Endif) {
+            let _: OpenParenSymbol = ts.parse()?;
+            while ts.peek::<(CloseParenSymbol, DotSymbol)>().is_none() {
+                args.push(ts.parse()?);
+            }
+            let _: CloseParenSymbol = ts.parse()?;
+        }
+        Ok(Self {
+            hyphen,
+            kind,
+            args,
+            dot: ts.parse()?,
+        })
+    }
+}
+
 /// `-` (`include` | `include_lib`) `(` `$PATH` `)` `.`
 ///
 /// - $PATH: [StringToken]
@@ -278,6 +579,10 @@ impl IncludeDirective {
         self.file.value()
     }
 
+    pub fn is_include_lib(&self) -> bool {
+        matches!(self.include, Either::B(_))
+    }
+
     pub fn var_substituted_path(&self) -> PathBuf {
         let path_str = self.file.value();
         let path: &Path = path_str.as_ref();
@@ -361,6 +666,21 @@ mod tests {
                         C,
                         D,
                         E), F)."},
+            indoc::indoc! {"
+            -define(G(X),
+                    is_integer(X),
+                    X > 0;
+                    is_atom(X))."},
+            indoc::indoc! {"
+            -define(T,
+                    {aaa, bbb} |
+                    [ccc])."},
+            indoc::indoc! {"
+            -define(C,
+                    foo(X) ->
+                        X;
+                    foo(_) ->
+                        error)."},
         ];
         for text in texts {
             crate::assert_format!(text, Form);
@@ -419,6 +739,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn doc_attr_works() {
+        let texts = [
+            "-doc false.",
+            "-moduledoc(\"Foo\").",
+            indoc::indoc! {r#"
+            -doc """
+              Foo *bar*.
+
+              Baz.
+              """."#},
+            indoc::indoc! {r#"
+            %---10---|%---20---|
+            -doc #{since =>
+                       "1.0",
+                   equiv =>
+                       foo(1)}."#},
+        ];
+        for text in texts {
+            crate::assert_format!(text, Form);
+        }
+    }
+
     #[test]
     fn record_decl_works() {
         let texts = [
@@ -1,5 +1,5 @@
 use crate::format::Format;
-use crate::items::tokens::KeywordToken;
+use crate::items::tokens::{KeywordToken, LexicalToken};
 use crate::parse::{self, Parse, TokenStream};
 use crate::span::Span;
 use erl_tokenize::values::Keyword;
@@ -8,11 +8,15 @@ macro_rules! impl_traits {
     ($name:ident,$value:ident) => {
         impl Parse for $name {
             fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
-                let token: KeywordToken = ts.parse()?;
-                if token.value() == Keyword::$value {
-                    Ok(Self(token))
-                } else {
-                    Err(parse::Error::unexpected_token(ts, token.into()))
+                let expected = || parse::Expected::Token(Keyword::$value.as_str());
+                match LexicalToken::parse(ts).map_err(|e| e.expected(expected()))? {
+                    LexicalToken::Keyword(token) if token.value() == Keyword::$value => {
+                        Ok(Self(token))
+                    }
+                    token => Err(
+                        parse::Error::unexpected_token(ts, token, ts.last_token_index())
+                            .expected(expected()),
+                    ),
                 }
             }
         }
@@ -67,10 +71,6 @@ impl_traits!(CaseKeyword, Case);
 pub struct CatchKeyword(KeywordToken);
 impl_traits!(CatchKeyword, Catch);
 
-#[derive(Debug, Clone, Span, Format)]
-pub struct CondKeyword(KeywordToken);
-impl_traits!(CondKeyword, Cond);
-
 #[derive(Debug, Clone, Span, Format)]
 pub struct DivKeyword(KeywordToken);
 impl_traits!(DivKeyword, Div);
@@ -87,10 +87,6 @@ impl_traits!(FunKeyword, Fun);
 pub struct IfKeyword(KeywordToken);
 impl_traits!(IfKeyword, If);
 
-#[derive(Debug, Clone, Span, Format)]
-pub struct LetKeyword(KeywordToken);
-impl_traits!(LetKeyword, Let);
-
 #[derive(Debug, Clone, Span, Format)]
 pub struct NotKeyword(KeywordToken);
 impl_traits!(NotKeyword, Not);
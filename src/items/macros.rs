@@ -1,10 +1,13 @@
 use crate::format::{Format, Formatter};
-use crate::items::components::{Args, Either, Maybe};
+use crate::items::components::{
+    Args, Clauses, Either, GuardDelimiter, Maybe, NonEmptyItems, WithGuard,
+};
+use crate::items::expressions::components::FunctionClause;
 use crate::items::symbols::{
     CloseParenSymbol, CommaSymbol, DotSymbol, OpenParenSymbol, QuestionSymbol,
 };
 use crate::items::tokens::{AtomToken, LexicalToken, StringToken, VariableToken};
-use crate::items::Expr;
+use crate::items::{Expr, Type};
 use crate::parse::{self, Parse, ResumeParse, TokenStream};
 use crate::span::{Position, Span};
 use erl_tokenize::values::{Keyword, Symbol};
@@ -47,13 +50,19 @@ impl Macro {
         self.args.get().map(|x| x.get().len())
     }
 
+    pub(crate) fn arg_tokens(&self) -> Option<Vec<&[LexicalToken]>> {
+        self.args
+            .get()
+            .map(|x| x.get().iter().map(|arg| arg.tokens()).collect())
+    }
+
+    /// Expands this macro call with the given definition.
     pub fn expand(
         &self,
-        variables: Option<Vec<String>>,
-        replacement: Vec<LexicalToken>,
-    ) -> Vec<LexicalToken> {
-        let args = if let (Some(vars), Some(vals)) = (&variables, self.args.get().map(|x| x.get()))
-        {
+        variables: Option<&[String]>,
+        replacement: &[LexicalToken],
+    ) -> Vec<ExpandedToken> {
+        let args = if let (Some(vars), Some(vals)) = (variables, self.args.get().map(|x| x.get())) {
             vars.iter()
                 .map(|x| &x[..])
                 .zip(vals.iter())
@@ -63,24 +72,34 @@ impl Macro {
         };
 
         let mut do_stringify = false;
-        let mut tokens = Vec::new();
+        let mut tokens = Vec::with_capacity(replacement.len());
         for token in replacement {
             match token {
-                LexicalToken::Variable(_) if do_stringify => {
-                    let dummy =
-                        StringToken::new("EFMT_DUMMY", self.start_position(), self.end_position());
-                    tokens.push(dummy.into());
+                LexicalToken::Variable(x) if do_stringify => {
+                    let value = args
+                        .get(x.value())
+                        .map_or_else(|| "EFMT_DUMMY".to_owned(), |arg| stringify(arg.tokens()));
+                    let token =
+                        StringToken::new(&value, self.start_position(), self.end_position());
+                    tokens.push(ExpandedToken::Body(token.into()));
                 }
                 LexicalToken::Variable(x) if args.contains_key(x.value()) => {
-                    tokens.extend(args[x.value()].tokens().iter().cloned());
+                    tokens.extend(
+                        args[x.value()]
+                            .tokens()
+                            .iter()
+                            .cloned()
+                            .map(ExpandedToken::Arg),
+                    );
                 }
                 LexicalToken::Symbol(x) if x.value() == Symbol::DoubleQuestion => {
                     do_stringify = true;
                     continue;
                 }
-                mut token => {
+                token => {
+                    let mut token = token.clone();
                     token.set_span(self);
-                    tokens.push(token);
+                    tokens.push(ExpandedToken::Body(token));
                 }
             }
             do_stringify = false;
@@ -89,6 +108,55 @@ impl Macro {
     }
 }
 
+// Converts `tokens` into a string in the same way as the Erlang preprocessor (i.e., `??Arg`).
+//
+// The tokens are joined with a space, e.g., `foo(1,  2)` is converted into `"foo ( 1 , 2 )"`.
+// As the tokens that came from a macro definition don't have the source text at their positions,
+// the text of each token is made from the token itself.
+fn stringify(tokens: &[LexicalToken]) -> String {
+    let position = erl_tokenize::Position::new();
+    tokens
+        .iter()
+        .map(|token| match token {
+            LexicalToken::Atom(x) => {
+                // Atoms are quoted only if needed (e.g., `'B'` or `'case'`).
+                let mut tokenizer = erl_tokenize::Tokenizer::new(x.value());
+                match (tokenizer.next(), tokenizer.next()) {
+                    (Some(Ok(erl_tokenize::Token::Atom(y))), None) if y.value() == x.value() => {
+                        x.value().to_owned()
+                    }
+                    _ => erl_tokenize::tokens::AtomToken::from_value(x.value(), position.clone())
+                        .text()
+                        .to_owned(),
+                }
+            }
+            LexicalToken::Char(x) => x.text().to_owned(),
+            LexicalToken::Float(x) => x.text().to_owned(),
+            LexicalToken::Integer(x) => x.value().to_owned(),
+            LexicalToken::Keyword(x) => x.value().as_str().to_owned(),
+            LexicalToken::String(x) if x.is_verbatim() => x.value().to_owned(),
+            LexicalToken::String(x) => {
+                erl_tokenize::tokens::StringToken::from_value(x.value(), position.clone())
+                    .text()
+                    .to_owned()
+            }
+            LexicalToken::Symbol(x) => x.value().as_str().to_owned(),
+            LexicalToken::Variable(x) => x.value().to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A token generated by [Macro::expand].
+#[derive(Debug, Clone)]
+pub enum ExpandedToken {
+    /// A token that came from the replacement of the macro definition.
+    Body(LexicalToken),
+
+    /// A token that came from an argument at the call site.
+    Arg(LexicalToken),
+}
+
 #[derive(Debug, Clone, Span, Parse, Format)]
 pub(crate) struct MacroName(Either<AtomToken, VariableToken>);
 
@@ -143,16 +211,30 @@ impl Parse for MacroReplacement {
     }
 }
 
+/// Tries to parse `tokens` as `T` and, if all of the tokens are consumed, formats the parsed item.
+fn try_format_tokens<T: Parse + Format + Span>(
+    fmt: &mut Formatter,
+    tokens: &[LexicalToken],
+    end_position: Position,
+) -> bool {
+    if let Ok(item) = fmt.token_stream_mut().parse_tokens::<T>(tokens.to_owned()) {
+        if item.end_position() == end_position {
+            item.format(fmt);
+            return true;
+        }
+    }
+    false
+}
+
 impl Format for MacroReplacement {
     fn format(&self, fmt: &mut Formatter) {
-        if let Ok(expr) = fmt
-            .token_stream_mut()
-            .parse_tokens::<Expr>(self.tokens.clone())
+        let end = self.end_position();
+        if try_format_tokens::<Expr>(fmt, &self.tokens, end)
+            || try_format_tokens::<NonEmptyItems<Expr, GuardDelimiter>>(fmt, &self.tokens, end)
+            || try_format_tokens::<Type>(fmt, &self.tokens, end)
+            || try_format_tokens::<Clauses<FunctionClause<AtomToken>>>(fmt, &self.tokens, end)
         {
-            if expr.end_position() == self.end_position() {
-                expr.format(fmt);
-                return;
-            }
+            return;
         }
 
         fmt.add_span(self);
@@ -184,7 +266,11 @@ impl Parse for MacroArg {
     fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
         if ts.peek::<Either<CommaSymbol, CloseParenSymbol>>().is_some() {
             let token = ts.parse()?;
-            return Err(parse::Error::unexpected_token(ts, token));
+            return Err(parse::Error::unexpected_token(
+                ts,
+                token,
+                ts.last_token_index(),
+            ));
         }
 
         let mut tokens = Vec::new();
@@ -207,7 +293,11 @@ impl Parse for MacroArg {
                     | Symbol::CloseSquare
                     | Symbol::DoubleRightAngle => {
                         if level == 0 {
-                            return Err(parse::Error::unexpected_token(ts, token));
+                            return Err(parse::Error::unexpected_token(
+                                ts,
+                                token,
+                                ts.last_token_index(),
+                            ));
                         }
                         level -= 1;
                     }
@@ -221,16 +311,19 @@ impl Parse for MacroArg {
                     | Keyword::Receive => {
                         level += 1;
                     }
-                    Keyword::Fun => {
-                        if ts.peek::<OpenParenSymbol>().is_some()
-                            || ts.peek::<(LexicalToken, OpenParenSymbol)>().is_some()
-                        {
-                            level += 1;
-                        }
+                    Keyword::Fun
+                        if (ts.peek::<OpenParenSymbol>().is_some()
+                            || ts.peek::<(LexicalToken, OpenParenSymbol)>().is_some()) =>
+                    {
+                        level += 1;
                     }
                     Keyword::End => {
                         if level == 0 {
-                            return Err(parse::Error::unexpected_token(ts, token));
+                            return Err(parse::Error::unexpected_token(
+                                ts,
+                                token,
+                                ts.last_token_index(),
+                            ));
                         }
                         level -= 1;
                     }
@@ -247,14 +340,16 @@ impl Parse for MacroArg {
 
 impl Format for MacroArg {
     fn format(&self, fmt: &mut Formatter) {
-        if let Ok(expr) = fmt
-            .token_stream_mut()
-            .parse_tokens::<Expr>(self.tokens.clone())
+        // Patterns are parsed as [Expr] and `$PATTERN when $GUARD` appears in e.g., `?assertMatch(..)`.
+        // As the arguments are split at the top-level commas (as `epp` does), a guard containing `,`
+        // is split into multiple arguments, and the rest of the guard is parsed as a guard sequence.
+        let end = self.end_position();
+        if try_format_tokens::<Expr>(fmt, &self.tokens, end)
+            || try_format_tokens::<WithGuard<Expr, Expr, GuardDelimiter>>(fmt, &self.tokens, end)
+            || try_format_tokens::<NonEmptyItems<Expr, GuardDelimiter>>(fmt, &self.tokens, end)
+            || try_format_tokens::<Type>(fmt, &self.tokens, end)
         {
-            if expr.end_position() == self.end_position() {
-                expr.format(fmt);
-                return;
-            }
+            return;
         }
 
         fmt.add_span(self);
@@ -399,6 +494,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn macro_arg_with_guard_works() {
+        let texts = [
+            indoc::indoc! {"
+            -define(M(A, B),
+                    ok).
+            foo() ->
+                ?M({ok, V}
+                     when V > 0,
+                   f()).
+            "},
+            indoc::indoc! {"
+            -define(M(A, B),
+                    ok).
+            foo() ->
+                ?M(X when X > 0;
+                          X < 0,
+                   f()).
+            "},
+        ];
+        for text in texts {
+            crate::assert_format!(text, Module);
+        }
+    }
+
+    #[test]
+    fn macro_arg_with_split_guard_works() {
+        // `epp` splits the arguments at the top-level commas even if they are in a guard.
+        let text = indoc::indoc! {"
+        -define(M(A, B, C), ok).
+        foo() -> ?M(X  when  X>0, X<10;is_integer( X ), f())."};
+        let expected = indoc::indoc! {"
+        -define(M(A, B, C), ok).
+        foo() ->
+            ?M(X when X > 0,
+               X < 10; is_integer(X),
+               f()).
+        "};
+        let formatted = crate::Options::new()
+            .max_columns(40)
+            .format_text::<Module>(text)
+            .unwrap();
+        similar_asserts::assert_str_eq!(formatted, expected);
+    }
+
     #[test]
     fn weird_macro_works() {
         let texts = [
@@ -517,4 +657,27 @@ mod tests {
             crate::assert_format!(text, Module);
         }
     }
+
+    #[test]
+    fn nested_macro_call_works() {
+        let texts = [
+            indoc::indoc! {"
+            %---10---|%---20---|
+            -define(ID(X), X).
+            foo() ->
+                ?ID(?ID(?ID(1))).
+            "},
+            indoc::indoc! {"
+            %---10---|%---20---|
+            -define(ID(X), X).
+            -define(a, ?ID(?b)).
+            -define(b, ?ID(?a)).
+            foo() ->
+                ?a.
+            "},
+        ];
+        for text in texts {
+            crate::assert_format!(text, Module);
+        }
+    }
 }
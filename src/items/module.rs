@@ -1,5 +1,5 @@
 use crate::format::{Format, Formatter};
-use crate::items::Form;
+use crate::items::{Config, Form};
 use crate::parse::{self, Parse, TokenStream};
 use crate::span::{Position, Span};
 
@@ -15,19 +15,66 @@ impl Parse for Module {
     fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
         let sof = ts.prev_token_end_position();
         let mut forms = Vec::new();
-        while !ts.is_eof()? {
+        loop {
+            // A tokenize error is handled as a broken form if the error recovery is enabled.
+            match ts.is_eof() {
+                Ok(true) => break,
+                Err(e) if !ts.recover_errors() || forms.is_empty() => return Err(e),
+                _ => {}
+            }
+
+            // `?FUNCTION_NAME` and `?FUNCTION_ARITY` are only valid within the form that sets them.
+            ts.clear_current_function();
+
+            if forms.is_empty() && ts.recover_errors() {
+                match ts.with_error_recovery_disabled(|ts| ts.parse()) {
+                    Ok(form) => {
+                        forms.push(form);
+                        continue;
+                    }
+                    // A config file is not parsed as a module starting with a broken form.
+                    Err(e) if ts.peek::<Config>().is_some() => return Err(e),
+                    Err(_) => {
+                        // Discards the errors of the above attempts to report the error of the broken form.
+                        ts.take_last_error();
+                    }
+                }
+            }
             forms.push(ts.parse()?);
         }
+        if !ts.allow_partial() {
+            if let Some(e) = ts.recovered_errors().first() {
+                return Err(e.clone());
+            }
+        }
         let eof = ts.next_token_start_position()?;
         Ok(Self { sof, forms, eof })
     }
 }
 
+impl Module {
+    pub(crate) fn forms(&self) -> &[Form] {
+        &self.forms
+    }
+}
+
 impl Format for Module {
     fn format(&self, fmt: &mut Formatter) {
+        let mut prev_form: Option<&Form> = None;
         for form in &self.forms {
+            if let Some(prev) = prev_form.filter(|_| fmt.normalize_blank_lines()) {
+                if (prev.is_fun_spec() && form.is_fun_decl()) || prev.is_doc() {
+                    // Specs and `-doc` attributes are kept attached to the following forms.
+                    fmt.remove_blank_line();
+                } else if prev.is_fun_decl()
+                    && (form.is_fun_decl() || form.is_fun_spec() || form.is_doc())
+                {
+                    fmt.add_blank_line();
+                }
+            }
             form.format(fmt);
             fmt.add_newline();
+            prev_form = Some(form);
         }
     }
 }
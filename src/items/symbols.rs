@@ -1,5 +1,5 @@
 use crate::format::Format;
-use crate::items::tokens::SymbolToken;
+use crate::items::tokens::{LexicalToken, SymbolToken};
 use crate::parse::{self, Parse, TokenStream};
 use crate::span::{Position, Span};
 use erl_tokenize::values::Symbol;
@@ -8,11 +8,15 @@ macro_rules! impl_traits {
     ($name:ident, $value:ident) => {
         impl Parse for $name {
             fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
-                let token: SymbolToken = ts.parse()?;
-                if token.value() == Symbol::$value {
-                    Ok(Self(token))
-                } else {
-                    Err(parse::Error::unexpected_token(ts, token.into()))
+                let expected = || parse::Expected::Token(Symbol::$value.as_str());
+                match LexicalToken::parse(ts).map_err(|e| e.expected(expected()))? {
+                    LexicalToken::Symbol(token) if token.value() == Symbol::$value => {
+                        Ok(Self(token))
+                    }
+                    token => Err(
+                        parse::Error::unexpected_token(ts, token, ts.last_token_index())
+                            .expected(expected()),
+                    ),
                 }
             }
         }
@@ -106,10 +110,6 @@ impl QuestionSymbol {
     }
 }
 
-#[derive(Debug, Clone, Span, Format)]
-pub struct DoubleQuestionSymbol(SymbolToken);
-impl_traits!(DoubleQuestionSymbol, DoubleQuestion);
-
 #[derive(Debug, Clone, Span, Format)]
 pub struct NotSymbol(SymbolToken);
 impl_traits!(NotSymbol, Not);
@@ -71,6 +71,11 @@ impl VisibleToken {
         if matches!((self, other), (Self::Comment(_), _) | (_, Self::Comment(_))) {
             return false;
         }
+        if matches!((self, other), (Self::String(x), _) | (_, Self::String(x)) if x.is_verbatim()) {
+            // The modifiers of a sigil would absorb a following name (e.g., `~"a"of`), and
+            // a triple-quoted string cannot be joined with an adjacent string.
+            return !matches!((self, other), (Self::Symbol(_), _) | (_, Self::Symbol(_)));
+        }
         if let (Self::Integer(_), Self::Symbol(b)) = (self, other) {
             if b.value() == Sharp {
                 return true;
@@ -103,6 +108,12 @@ impl VisibleToken {
 
 macro_rules! impl_traits {
     ($name:ident, $variant:ident) => {
+        impl_traits!($name, $variant, None);
+    };
+    ($name:ident, $variant:ident, $kind:literal) => {
+        impl_traits!($name, $variant, Some(parse::Expected::Kind($kind)));
+    };
+    ($name:ident, $variant:ident, $kind:expr) => {
         impl Span for $name {
             fn start_position(&self) -> Position {
                 self.start
@@ -115,9 +126,18 @@ macro_rules! impl_traits {
 
         impl Parse for $name {
             fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
-                match ts.parse()? {
+                let kind: Option<parse::Expected> = $kind;
+                let expected = |e: parse::Error| match kind {
+                    Some(kind) => e.expected(kind),
+                    None => e,
+                };
+                match LexicalToken::parse(ts).map_err(expected)? {
                     LexicalToken::$variant(token) => Ok(token),
-                    token => Err(parse::Error::unexpected_token(ts, token)),
+                    token => Err(expected(parse::Error::unexpected_token(
+                        ts,
+                        token,
+                        ts.last_token_index(),
+                    ))),
                 }
             }
         }
@@ -163,7 +183,7 @@ impl AtomToken {
     }
 }
 
-impl_traits!(AtomToken, Atom);
+impl_traits!(AtomToken, Atom, "atom");
 
 impl Element for AtomToken {
     fn is_packable(&self) -> bool {
@@ -173,17 +193,28 @@ impl Element for AtomToken {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct CharToken {
+    /// The source text of the literal.
+    #[serde(default)]
+    text: String,
     start: Position,
     end: Position,
 }
 
 impl CharToken {
-    pub fn new(start: Position, end: Position) -> Self {
-        Self { start, end }
+    pub fn new(text: &str, start: Position, end: Position) -> Self {
+        Self {
+            text: text.to_owned(),
+            start,
+            end,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
     }
 }
 
-impl_traits!(CharToken, Char);
+impl_traits!(CharToken, Char, "character");
 
 impl Element for CharToken {
     fn is_packable(&self) -> bool {
@@ -193,17 +224,28 @@ impl Element for CharToken {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct FloatToken {
+    /// The source text of the literal.
+    #[serde(default)]
+    text: String,
     start: Position,
     end: Position,
 }
 
 impl FloatToken {
-    pub fn new(start: Position, end: Position) -> Self {
-        Self { start, end }
+    pub fn new(text: &str, start: Position, end: Position) -> Self {
+        Self {
+            text: text.to_owned(),
+            start,
+            end,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
     }
 }
 
-impl_traits!(FloatToken, Float);
+impl_traits!(FloatToken, Float, "float");
 
 impl Element for FloatToken {
     fn is_packable(&self) -> bool {
@@ -213,17 +255,25 @@ impl Element for FloatToken {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct IntegerToken {
+    /// The decimal representation of the value.
+    #[serde(default)]
+    value: String,
     start: Position,
     end: Position,
 }
 
 impl IntegerToken {
-    pub fn new(start: Position, end: Position) -> Self {
-        Self { start, end }
+    pub fn new(value: String, start: Position, end: Position) -> Self {
+        Self { value, start, end }
+    }
+
+    /// Returns the decimal representation of the value (e.g., `"255"` for `16#FF`).
+    pub fn value(&self) -> &str {
+        &self.value
     }
 }
 
-impl_traits!(IntegerToken, Integer);
+impl_traits!(IntegerToken, Integer, "integer");
 
 impl Element for IntegerToken {
     fn is_packable(&self) -> bool {
@@ -255,6 +305,8 @@ pub struct StringToken {
     value: String,
     start: Position,
     end: Position,
+    #[serde(default)]
+    is_verbatim: bool,
 }
 
 impl StringToken {
@@ -263,19 +315,37 @@ impl StringToken {
             value: value.to_owned(),
             start,
             end,
+            is_verbatim: false,
+        }
+    }
+
+    /// Makes a token for a triple-quoted string or a sigil (OTP 27).
+    ///
+    /// As such a literal is always written verbatim, its value is the literal text itself.
+    pub fn new_verbatim(text: &str, start: Position, end: Position) -> Self {
+        Self {
+            value: text.to_owned(),
+            start,
+            end,
+            is_verbatim: true,
         }
     }
 
     pub fn value(&self) -> &str {
         &self.value
     }
+
+    /// Returns `true` if this token is a triple-quoted string or a sigil.
+    pub fn is_verbatim(&self) -> bool {
+        self.is_verbatim
+    }
 }
 
-impl_traits!(StringToken, String);
+impl_traits!(StringToken, String, "string");
 
 impl Element for StringToken {
     fn is_packable(&self) -> bool {
-        true
+        !self.is_verbatim
     }
 }
 
@@ -319,7 +389,7 @@ impl VariableToken {
     }
 }
 
-impl_traits!(VariableToken, Variable);
+impl_traits!(VariableToken, Variable, "variable");
 
 impl Element for VariableToken {
     fn is_packable(&self) -> bool {
@@ -2,7 +2,7 @@
 //!
 //! <https://www.erlang.org/doc/reference_manual/typespec.html>
 use self::components::{BinaryOp, BitstringItem, UnaryOp};
-use crate::format::{Format, Formatter, Indent, Newline};
+use crate::format::{item_kind, Format, Formatter, Indent, Newline};
 use crate::items::components::{
     Args, BinaryOpLike, BinaryOpStyle, BitstringLike, Either, Element, ListLike, MapLike, Maybe,
     NonEmptyItems, Params, Parenthesized, TupleLike, UnaryOpLike,
@@ -76,6 +76,7 @@ pub struct AnnotatedVariableType {
 }
 
 impl Format for AnnotatedVariableType {
+    #[item_kind("AnnotatedVariableType")]
     fn format(&self, fmt: &mut Formatter) {
         self.variable.format(fmt);
         fmt.add_space();
@@ -110,6 +111,7 @@ pub struct FunctionType {
 }
 
 impl Format for FunctionType {
+    #[item_kind("FunctionType")]
     fn format(&self, fmt: &mut Formatter) {
         self.fun.format(fmt);
         fmt.add_space();
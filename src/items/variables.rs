@@ -1,6 +1,6 @@
 use crate::format::Format;
 use crate::items::components::Element;
-use crate::items::tokens::VariableToken;
+use crate::items::tokens::{LexicalToken, VariableToken};
 use crate::parse::{self, Parse, TokenStream};
 use crate::span::Span;
 
@@ -8,11 +8,13 @@ macro_rules! impl_parse {
     ($name:ident, $value:expr) => {
         impl Parse for $name {
             fn parse(ts: &mut TokenStream) -> parse::Result<Self> {
-                let token: VariableToken = ts.parse()?;
-                if token.value() == $value {
-                    Ok(Self(token))
-                } else {
-                    Err(parse::Error::unexpected_token(ts, token.into()))
+                let expected = || parse::Expected::Token($value);
+                match LexicalToken::parse(ts).map_err(|e| e.expected(expected()))? {
+                    LexicalToken::Variable(token) if token.value() == $value => Ok(Self(token)),
+                    token => Err(
+                        parse::Error::unexpected_token(ts, token, ts.last_token_index())
+                            .expected(expected()),
+                    ),
                 }
             }
         }
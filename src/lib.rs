@@ -1,5 +1,10 @@
-use crate::format::{Format, Formatter};
+use crate::format::{ColumnWidth, Format, Formatter};
+use crate::items::Module;
+use crate::missing::MissingReport;
 use crate::parse::{IncludeOptions, Parse, TokenStream};
+use crate::rewrite::{RewriteRule, Source};
+use anyhow::Context as _;
+use std::collections::BTreeSet;
 use std::path::Path;
 
 pub mod diff;
@@ -7,9 +12,11 @@ pub mod error;
 pub mod files;
 pub mod format;
 pub mod items;
+pub mod missing;
 pub mod parse;
 #[cfg(feature = "pprof")]
 pub mod profile;
+pub mod rewrite;
 pub mod span;
 
 pub(crate) mod erl;
@@ -28,14 +35,34 @@ pub fn format_text<T: Parse + Format>(text: &str) -> anyhow::Result<String> {
 #[derive(Debug, Clone)]
 pub struct Options {
     max_columns: usize,
+    column_width: ColumnWidth,
     include: IncludeOptions,
+    otp_release: u32,
+    align_trailing_comments: bool,
+    reflow_comments: bool,
+    normalize_blank_lines: bool,
+    remove_clause_body_leading_blank_lines: bool,
+    rewrite_rules: BTreeSet<RewriteRule>,
+    allow_partial: bool,
+    deny_missing_macros: bool,
+    missing_report: Option<MissingReport>,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             max_columns: Self::DEFAULT_MAX_COLUMNS,
+            column_width: ColumnWidth::default(),
             include: IncludeOptions::default(),
+            otp_release: TokenStream::DEFAULT_OTP_RELEASE,
+            align_trailing_comments: false,
+            reflow_comments: false,
+            normalize_blank_lines: false,
+            remove_clause_body_leading_blank_lines: false,
+            allow_partial: false,
+            deny_missing_macros: false,
+            missing_report: None,
+            rewrite_rules: BTreeSet::new(),
         }
     }
 }
@@ -54,6 +81,14 @@ impl Options {
         self
     }
 
+    /// Sets how to count the width of a text in columns (the default is [ColumnWidth::Unicode]).
+    ///
+    /// [ColumnWidth::Chars] and [ColumnWidth::Bytes] are provided for compatibility.
+    pub fn column_width(mut self, column_width: ColumnWidth) -> Self {
+        self.column_width = column_width;
+        self
+    }
+
     pub fn disable_include(mut self) -> Self {
         self.include = self.include.disable_include();
         self
@@ -71,28 +106,245 @@ impl Options {
         self
     }
 
+    /// Sets the OTP release number that is used as the value of `?OTP_RELEASE` macro.
+    pub fn otp_release(mut self, release: u32) -> Self {
+        self.otp_release = release;
+        self
+    }
+
+    /// Aligns trailing comments on consecutive lines to the same column.
+    pub fn align_trailing_comments(mut self) -> Self {
+        self.align_trailing_comments = true;
+        self
+    }
+
+    /// Rewraps overlong `%%` comment blocks to fit in the max columns.
+    ///
+    /// Single `%` comments, `%%%` comments and comments between `efmt:off` and `efmt:on` are kept as they are.
+    pub fn reflow_comments(mut self) -> Self {
+        self.reflow_comments = true;
+        self
+    }
+
+    /// Puts exactly one blank line between function definitions.
+    ///
+    /// A spec and the following function definition are kept together.
+    /// Blank lines between other forms (e.g., `-export` or `-include` attributes) are preserved as they are.
+    pub fn normalize_blank_lines(mut self) -> Self {
+        self.normalize_blank_lines = true;
+        self
+    }
+
+    /// Removes blank lines at the start of clause bodies.
+    pub fn remove_clause_body_leading_blank_lines(mut self) -> Self {
+        self.remove_clause_body_leading_blank_lines = true;
+        self
+    }
+
+    /// Keeps the forms that could not be parsed as they are, instead of failing to format the whole text.
+    ///
+    /// A form is assumed to end with a `.` that is followed by a newline and a line starting at column 0.
+    /// The parse errors of the skipped forms are reported as warnings.
+    /// Note that the first form is never skipped so that a config file is not taken as a broken module.
+    ///
+    /// Without this option, the forms are skipped in the same way only to report all of the errors.
+    pub fn allow_partial(mut self) -> Self {
+        self.allow_partial = true;
+        self
+    }
+
+    /// Makes formatting fail if the text uses undefined macros or includes files that could not be resolved.
+    ///
+    /// Note that all macros defined in include files are undefined if [Options::disable_include] is set.
+    pub fn deny_missing_macros(mut self) -> Self {
+        self.deny_missing_macros = true;
+        self
+    }
+
+    /// Adds the undefined macros and unresolved include files of the formatted texts to `report`.
+    pub fn report_missing(mut self, report: MissingReport) -> Self {
+        self.missing_report = Some(report);
+        self
+    }
+
+    /// Enables a rewrite rule.
+    ///
+    /// Unlike the other options, this changes the token sequence of the formatted text.
+    pub fn rewrite(mut self, rule: RewriteRule) -> Self {
+        self.rewrite_rules.insert(rule);
+        self
+    }
+
     pub fn format_file<T: Parse + Format, P: AsRef<Path>>(self, path: P) -> anyhow::Result<String> {
         let text = std::fs::read_to_string(&path)?;
-        let mut tokenizer = erl_tokenize::Tokenizer::new(text);
-        tokenizer.set_filepath(path);
-        self.format::<T>(tokenizer)
+        self.format_text_with_path::<T, _>(&text, path)
+    }
+
+    /// Formats `text` as the content of the file located at `path` (the file itself is not read).
+    ///
+    /// `path` is used to search for include files and in error messages.
+    pub fn format_text_with_path<T: Parse + Format, P: AsRef<Path>>(
+        self,
+        text: &str,
+        path: P,
+    ) -> anyhow::Result<String> {
+        let text = self.rewrite_text(text, Some(path.as_ref()))?;
+        self.format_rewritten_text::<T>(&text, Some(path.as_ref()))
     }
 
     pub fn format_text<T: Parse + Format>(self, text: &str) -> anyhow::Result<String> {
-        let tokenizer = erl_tokenize::Tokenizer::new(text.to_owned());
+        let text = self.rewrite_text(text, None)?;
+        self.format_rewritten_text::<T>(&text, None)
+    }
+
+    /// Formats `text` returned by [Options::rewrite_text] without applying the rewrite rules again.
+    ///
+    /// This is useful to get both the rewritten text and the formatted text without parsing the text twice
+    /// for the rewrites.
+    pub fn format_rewritten_text<T: Parse + Format>(
+        self,
+        text: &str,
+        path: Option<&Path>,
+    ) -> anyhow::Result<String> {
+        let mut tokenizer = erl_tokenize::Tokenizer::new(text.to_owned());
+        if let Some(path) = path {
+            tokenizer.set_filepath(path);
+        }
         self.format::<T>(tokenizer)
     }
 
+    /// Applies the enabled rewrite rules (see [Options::rewrite]) to `text`.
+    ///
+    /// This is a part of [Options::format_file] and [Options::format_text].
+    /// The formatted text has the same token sequence as the text returned by this method.
+    ///
+    /// If `text` is not an Erlang module, it is returned as it is.
+    pub fn rewrite_text(&self, text: &str, path: Option<&Path>) -> anyhow::Result<String> {
+        let mut text = text.to_owned();
+        let mut module = None;
+        for rule in &self.rewrite_rules {
+            let original = match module.take() {
+                Some(module) => module,
+                None => match self.parse_module(&text, path) {
+                    Ok(module) => module,
+                    Err(_) => return Ok(text),
+                },
+            };
+            let original = Source {
+                text: &text,
+                path,
+                module: original,
+            };
+
+            let edits = rule.get().rewrite(&original);
+            if edits.is_empty() {
+                module = Some(original.module);
+                continue;
+            }
+
+            let rewritten_text = rewrite::apply_edits(&text, edits);
+            let rewritten = Source {
+                text: &rewritten_text,
+                path,
+                module: self.parse_module(&rewritten_text, path)?,
+            };
+            rule.get().check(&original, &rewritten).with_context(|| {
+                format!("Failed to apply {:?} rewrite (maybe efmt bug)", rule.name())
+            })?;
+            module = Some(rewritten.module);
+            text = rewritten_text;
+        }
+        Ok(text)
+    }
+
+    fn parse_module(&self, text: &str, path: Option<&Path>) -> parse::Result<Module> {
+        let mut tokenizer = erl_tokenize::Tokenizer::new(text.to_owned());
+        if let Some(path) = path {
+            tokenizer.set_filepath(path);
+        }
+        let mut ts = TokenStream::new(tokenizer, self.include.clone());
+        ts.set_otp_release(self.otp_release);
+        ts.parse()
+    }
+
+    /// Explains why the line `line` (1-origin) of the formatted text of the file is laid out as it is.
+    ///
+    /// Note that `line` is a line number in the formatted text, not in the file.
+    /// The explanation shows the item kinds enclosing the line (e.g., `FunDecl > FunctionClause > BinaryOpCallExpr(=)`),
+    /// the `Indent` and `Newline` policies of the regions in the line and whether the regions fell back
+    /// to another layout because the line was too long (`LineTooLong`) or spanned multiple lines (`MultiLine`).
+    pub fn explain_file<T: Parse + Format, P: AsRef<Path>>(
+        self,
+        path: P,
+        line: usize,
+    ) -> anyhow::Result<String> {
+        let text = std::fs::read_to_string(&path)?;
+        let text = self.rewrite_text(&text, Some(path.as_ref()))?;
+        let mut tokenizer = erl_tokenize::Tokenizer::new(text);
+        tokenizer.set_filepath(path);
+        let max_columns = self.max_columns;
+        let align_trailing_comments = self.align_trailing_comments;
+        let formatter = self.formatter::<T>(tokenizer, true)?;
+        Ok(formatter.explain(max_columns, align_trailing_comments, line))
+    }
+
     fn format<T: Parse + Format>(
         self,
         tokenizer: erl_tokenize::Tokenizer<String>,
     ) -> anyhow::Result<String> {
+        let max_columns = self.max_columns;
+        let align_trailing_comments = self.align_trailing_comments;
+        let formatter = self.formatter::<T>(tokenizer, false)?;
+        Ok(formatter.format(max_columns, align_trailing_comments))
+    }
+
+    fn formatter<T: Parse + Format>(
+        self,
+        tokenizer: erl_tokenize::Tokenizer<String>,
+        explain: bool,
+    ) -> anyhow::Result<Formatter> {
         let mut ts = TokenStream::new(tokenizer, self.include);
-        let item: T = ts.parse()?;
+        ts.set_otp_release(self.otp_release);
+        ts.set_allow_partial(self.allow_partial);
+        // Broken forms are skipped even if partial formatting is not allowed to report the errors in the following forms too.
+        ts.set_recover_errors(true);
+        let item: T = match ts.parse() {
+            Ok(item) => item,
+            Err(_) if !self.allow_partial && !ts.recovered_errors().is_empty() => {
+                return Err(parse::Errors(ts.recovered_errors().to_vec()).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if self.allow_partial {
+            for e in ts.recovered_errors() {
+                log::warn!("Kept a form that could not be parsed as it is.\n{}", e);
+            }
+        }
+
+        let missing = ts.missing();
+        let path = ts.filepath();
+        if let Some(report) = &self.missing_report {
+            report.add(path.as_deref().map(|x| x.as_path()), &missing);
+        }
+        if self.deny_missing_macros && !missing.is_empty() {
+            anyhow::bail!(
+                "Found undefined macros or unresolved include files:{}",
+                missing
+                    .error_messages(&ts.text(), path.as_deref().map(|x| x.as_path()))
+                    .join("\n")
+            );
+        }
+
         let mut formatter = Formatter::new(ts);
+        formatter.set_column_width(self.column_width);
+        formatter.set_reflow_comments(self.reflow_comments);
+        formatter.set_normalize_blank_lines(self.normalize_blank_lines);
+        formatter.set_remove_clause_body_leading_blank_lines(
+            self.remove_clause_body_leading_blank_lines,
+        );
+        formatter.set_explain(explain);
         item.format(&mut formatter);
-        let formatted_text = formatter.format(self.max_columns);
-        Ok(formatted_text)
+        Ok(formatter)
     }
 }
 
@@ -100,7 +352,7 @@ impl Options {
 #[macro_export]
 macro_rules! assert_format {
     ($text:expr, $item_type:ident) => {{
-        let formatted = crate::Options::new()
+        let formatted = $crate::Options::new()
             .max_columns(20)
             .format_text::<$item_type>(&$text)
             .unwrap();
@@ -1,5 +1,8 @@
 use anyhow::Context;
+use efmt::format::ColumnWidth;
 use efmt::items::ModuleOrConfig;
+use efmt::missing::MissingReport;
+use efmt::rewrite::RewriteRule;
 use env_logger::Env;
 use rayon::iter::{IntoParallelIterator as _, ParallelIterator};
 use std::io::Read as _;
@@ -17,6 +20,15 @@ struct Opt {
     #[structopt(long, default_value = "120")]
     print_width: usize,
 
+    /// How to count the width of a text in columns (unicode, chars or bytes).
+    ///
+    /// `unicode` counts the display width of each grapheme cluster
+    /// (e.g., CJK characters take two columns and combining characters don't take a column by themselves).
+    /// `chars` and `bytes` are provided for compatibility.
+    /// This applies to both the print width and the alignment of items.
+    #[structopt(long, default_value = "unicode")]
+    column_width: ColumnWidth,
+
     /// Checks if input is formatted correctly.
     ///
     /// If so, exits with 0. Otherwise, exits with 1 and shows a diff.
@@ -28,11 +40,25 @@ struct Opt {
     write: bool,
 
     /// Shows the target input files.
-    ///
-    /// You can use this flag to exclude some files from the default target, e.g., `$ efmt $(efmt --show-files | grep -v rebar.config)`.
     #[structopt(long, conflicts_with = "check", conflicts_with = "write")]
     show_files: bool,
 
+    /// Excludes the files matching the given pattern from the default target files and directory arguments
+    /// (this option can be specified multiple times).
+    ///
+    /// The pattern is written in the gitignore syntax (e.g., `_build/` or `*_parser.erl`).
+    /// The pattern is relative to the current directory.
+    /// The patterns in the `.efmtignore` files in the root directory of the git repository, the current directory
+    /// and the directory arguments are also applied (relative to the directories containing them).
+    #[structopt(long = "exclude", number_of_values = 1)]
+    exclude_patterns: Vec<String>,
+
+    /// The file path of the text given via the standard input (i.e., `-`).
+    ///
+    /// The path is used to search for include files and in error messages, but the file itself is not read.
+    #[structopt(long)]
+    stdin_filepath: Option<PathBuf>,
+
     /// Outputs debug log messages.
     #[structopt(long)]
     verbose: bool,
@@ -46,9 +72,11 @@ struct Opt {
     /// Format target files.
     ///
     /// `-` means the standard input.
+    /// Directories are replaced with the target files in them (in the same way as the default target files).
     /// If no files are specified and any of `-c`, `-w` or `--show-files` options is specified,
-    /// All of the files named `**.{hrl,erl,app.src}` and `**/rebar.config` are used as the default
+    /// All of the files named `**.{hrl,erl,app.src,escript,config.src,app,appup,relup}` are used as the default
     /// (note that files spcified by `.gitignore` will be ignored).
+    /// `*.config` files are included only if they are `{rebar,sys,relx,elvis}.config` or located in `config/` directories.
     files: Vec<PathBuf>,
 
     /// Executes formatting in parallel.
@@ -68,6 +96,91 @@ struct Opt {
     #[structopt(long)]
     disable_include_cache: bool,
 
+    /// OTP release number used as the value of `?OTP_RELEASE` macro.
+    ///
+    /// This also affects the values of `?FEATURE_AVAILABLE(..)` and `?FEATURE_ENABLED(..)` macros.
+    /// [default: the latest OTP release supported by efmt]
+    #[structopt(long)]
+    otp_release: Option<u32>,
+
+    /// Aligns trailing comments on consecutive lines to the same column.
+    ///
+    /// If an aligned comment exceeds the print width, two spaces are put before the comment instead.
+    #[structopt(long)]
+    align_trailing_comments: bool,
+
+    /// Rewraps overlong `%%` comment blocks to fit in the print width.
+    ///
+    /// Single `%` comments, `%%%` comments, the structure of EDoc tags and comments between `efmt:off` and `efmt:on`
+    /// are kept as they are.
+    #[structopt(long)]
+    reflow_comments: bool,
+
+    /// Puts exactly one blank line between function definitions.
+    ///
+    /// A spec and the following function definition are kept together.
+    /// Blank lines between other forms (e.g., `-export` or `-include` attributes) are preserved as they are.
+    #[structopt(long)]
+    normalize_blank_lines: bool,
+
+    /// Removes blank lines at the start of clause bodies.
+    #[structopt(long)]
+    remove_clause_body_leading_blank_lines: bool,
+
+    /// Keeps the forms that could not be parsed as they are, instead of failing to format the whole file.
+    ///
+    /// A form is assumed to end with a `.` that is followed by a newline and a line starting at column 0.
+    /// The parse errors of the skipped forms are reported as warnings.
+    /// To overwrite files with `--write`, `--allow-partial-write` must also be specified.
+    #[structopt(long)]
+    allow_partial: bool,
+
+    /// Allows `--write` to overwrite files containing forms that could not be parsed (requires `--allow-partial`).
+    ///
+    /// Without this option, `--allow-partial` cannot be combined with `--write`
+    /// so that broken files are not overwritten by mistake.
+    #[structopt(long, requires = "allow-partial")]
+    allow_partial_write: bool,
+
+    /// Shows a summary of the unresolved `-include` and `-include_lib` files and the undefined macros
+    /// with the files using them at the end of the run.
+    #[structopt(long)]
+    report_missing: bool,
+
+    /// Fails to format the files that use undefined macros or include files that could not be resolved.
+    ///
+    /// This is useful to ensure that the include search directories are configured correctly.
+    /// Note that all macros defined in include files are undefined if `--disable-include` is specified.
+    #[structopt(long)]
+    deny_missing_macros: bool,
+
+    #[structopt(skip)]
+    missing_report: MissingReport,
+
+    /// Enables a rewrite rule (this option can be specified multiple times).
+    ///
+    /// Unlike the other options, rewrite rules change the token sequence of the formatted text:
+    /// - spec-parens: `-spec(foo() -> ok).` => `-spec foo() -> ok.` (also `-callback`, `-type` and `-opaque`)
+    /// - concat-strings: `"foo" "bar"` => `"foobar"`
+    /// - redundant-parens: removes the parentheses enclosing a single variable or literal in function definitions
+    /// - sort-exports: sorts and deduplicates `-export` and `-export_type` lists, and merges consecutive ones
+    /// - sort-includes: sorts and deduplicates adjacent `-include` (or `-include_lib`) directives
+    #[structopt(long = "rewrite", number_of_values = 1)]
+    rewrite_rules: Vec<RewriteRule>,
+
+    /// Explains why the given line of the formatted text of the file is laid out as it is (e.g., `src/foo.erl:12`).
+    ///
+    /// Note that LINE is a line number in the formatted text (i.e., the output of `efmt FILE` with the same options),
+    /// not in the original file.
+    /// This shows the item kinds enclosing the line, the `Indent` and `Newline` policies of the regions in the line
+    /// and whether the regions fell back to another layout due to `LineTooLong` or `MultiLine`.
+    #[structopt(
+        long,
+        value_name = "FILE:LINE",
+        conflicts_with_all = &["check", "write", "show-files"]
+    )]
+    explain: Option<ExplainTarget>,
+
     /// Enable profiling by `pprof`. The profile report will be generated in `flamegraph.svg`.
     #[cfg(feature = "pprof")]
     #[structopt(long)]
@@ -75,12 +188,16 @@ struct Opt {
 }
 
 impl Opt {
-    fn collect_default_files_if_need(&mut self) -> anyhow::Result<()> {
-        if !self.files.is_empty() || !(self.check || self.write || self.show_files) {
+    fn collect_target_files(&mut self) -> anyhow::Result<()> {
+        if !self.files.is_empty() {
+            self.files = efmt::files::expand_target_paths(&self.files, &self.exclude_patterns)?;
+            return Ok(());
+        }
+        if !(self.check || self.write || self.show_files) {
             return Ok(());
         }
 
-        self.files = efmt::files::collect_default_target_files()?;
+        self.files = efmt::files::collect_default_target_files(&self.exclude_patterns)?;
         if !self.files.is_empty() && !self.show_files {
             log::info!(
                 "The following files were added as the default input files:\n{}",
@@ -97,17 +214,58 @@ impl Opt {
     fn to_format_options(&self) -> efmt::Options {
         let mut format_options = efmt::Options::new()
             .max_columns(self.print_width)
+            .column_width(self.column_width)
             .include_dirs(self.include_dirs.clone());
 
+        if let Some(release) = self.otp_release {
+            format_options = format_options.otp_release(release);
+        }
+
         if !self.disable_include_cache {
             format_options = format_options.include_cache_dir(self.include_cache_dir.clone());
         }
         if self.disable_include {
             format_options = format_options.disable_include();
         }
+        if self.align_trailing_comments {
+            format_options = format_options.align_trailing_comments();
+        }
+        if self.reflow_comments {
+            format_options = format_options.reflow_comments();
+        }
+        if self.normalize_blank_lines {
+            format_options = format_options.normalize_blank_lines();
+        }
+        if self.remove_clause_body_leading_blank_lines {
+            format_options = format_options.remove_clause_body_leading_blank_lines();
+        }
+        if self.allow_partial {
+            format_options = format_options.allow_partial();
+        }
+        if self.deny_missing_macros {
+            format_options = format_options.deny_missing_macros();
+        }
+        if self.report_missing {
+            format_options = format_options.report_missing(self.missing_report.clone());
+        }
+        for rule in &self.rewrite_rules {
+            format_options = format_options.rewrite(*rule);
+        }
 
         format_options
     }
+
+    fn print_missing_report(&self) {
+        if !self.report_missing {
+            return;
+        }
+        eprintln!();
+        if self.missing_report.is_empty() {
+            eprintln!("No unresolved include files or undefined macros were found.");
+        } else {
+            eprint!("{}", self.missing_report);
+        }
+    }
 }
 
 fn main() -> anyhow::Result<()> {
@@ -124,7 +282,20 @@ fn main() -> anyhow::Result<()> {
 }
 
 fn main_with_opt(mut opt: Opt) -> anyhow::Result<()> {
-    opt.collect_default_files_if_need()?;
+    if let Some(target) = &opt.explain {
+        let explanation = opt
+            .to_format_options()
+            .explain_file::<ModuleOrConfig, _>(&target.path, target.line)?;
+        print!("{}", explanation);
+        return Ok(());
+    }
+
+    anyhow::ensure!(
+        !(opt.allow_partial && opt.write) || opt.allow_partial_write,
+        "`--allow-partial` cannot be combined with `--write` unless `--allow-partial-write` is specified"
+    );
+
+    opt.collect_target_files()?;
     if opt.files.is_empty() {
         Opt::clap().print_help()?;
         println!();
@@ -138,46 +309,67 @@ fn main_with_opt(mut opt: Opt) -> anyhow::Result<()> {
             }
         }
         Ok(())
-    } else if opt.check {
-        check_files(&opt)
     } else {
-        format_files(&opt)
+        let result = if opt.check {
+            check_files(&opt)
+        } else {
+            format_files(&opt)
+        };
+        opt.print_missing_report();
+        result
     }
 }
 
-fn format_file<P: AsRef<Path>>(
-    format_options: &efmt::Options,
-    path: P,
-) -> anyhow::Result<(String, String)> {
-    let original = std::fs::read_to_string(&path)?;
-    let formatted = format_options
-        .clone()
-        .format_file::<ModuleOrConfig, _>(path)?;
-    Ok((original, formatted))
+#[derive(Debug)]
+struct ExplainTarget {
+    path: PathBuf,
+    line: usize,
 }
 
-fn format_stdin(format_options: &efmt::Options) -> anyhow::Result<(String, String)> {
-    let mut original = String::new();
-    std::io::stdin().lock().read_to_string(&mut original)?;
-    let formatted = format_options
-        .clone()
-        .format_text::<ModuleOrConfig>(&original)?;
-    Ok((original, formatted))
+impl std::str::FromStr for ExplainTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (path, line) = s
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow::anyhow!("expected `FILE:LINE`, but got {:?}", s))?;
+        let line = line
+            .parse()
+            .with_context(|| format!("invalid line number {:?}", line))?;
+        anyhow::ensure!(line > 0, "line number must be 1 or greater");
+        Ok(Self {
+            path: PathBuf::from(path),
+            line,
+        })
+    }
 }
 
 fn format_file_or_stdin<P: AsRef<Path>>(
+    opt: &Opt,
     format_options: &efmt::Options,
     path: P,
 ) -> anyhow::Result<(String, String)> {
-    let (original, formatted) = if path.as_ref().to_str() == Some("-") {
-        format_stdin(format_options)
+    let is_stdin = path.as_ref().to_str() == Some("-");
+    let (original, file_path) = if is_stdin {
+        let mut original = String::new();
+        std::io::stdin().lock().read_to_string(&mut original)?;
+        (original, opt.stdin_filepath.as_deref())
     } else {
-        format_file(format_options, &path)
-    }?;
-    validate_formatted_text(path, &original, &formatted).context(concat!(
-        "Found a token mismatch between the original text ",
-        "and the formatted one (maybe efmt bug)"
-    ))?;
+        (std::fs::read_to_string(&path)?, Some(path.as_ref()))
+    };
+
+    // The rewritten text is also used to validate the formatted text.
+    let rewritten = format_options.rewrite_text(&original, file_path)?;
+    let formatted = format_options
+        .clone()
+        .format_rewritten_text::<ModuleOrConfig>(&rewritten, file_path)?;
+    let display_path = file_path.unwrap_or(path.as_ref());
+    validate_formatted_text(display_path, &rewritten, &formatted, opt.reflow_comments).context(
+        concat!(
+            "Found a token mismatch between the original text ",
+            "and the formatted one (maybe efmt bug)"
+        ),
+    )?;
     Ok((original, formatted))
 }
 
@@ -185,7 +377,7 @@ fn format_files(opt: &Opt) -> anyhow::Result<()> {
     let format_options = opt.to_format_options();
 
     fn do_format(opt: &Opt, format_options: &efmt::Options, file: &Path) -> anyhow::Result<()> {
-        match format_file_or_stdin(format_options, file) {
+        match format_file_or_stdin(opt, format_options, file) {
             Err(e) => {
                 log::error!("Failed to format {:?}\n{:?}", file, e);
                 Err(e)
@@ -245,8 +437,8 @@ fn format_files(opt: &Opt) -> anyhow::Result<()> {
 fn check_files(opt: &Opt) -> anyhow::Result<()> {
     let format_options = opt.to_format_options();
 
-    fn do_check(format_options: &efmt::Options, file: &Path) -> bool {
-        match format_file_or_stdin(format_options, file) {
+    fn do_check(opt: &Opt, format_options: &efmt::Options, file: &Path) -> bool {
+        match format_file_or_stdin(opt, format_options, file) {
             Err(e) => {
                 log::error!("Failed to format {:?}\n{:?}", file, e);
                 false
@@ -268,12 +460,12 @@ fn check_files(opt: &Opt) -> anyhow::Result<()> {
         opt.files
             .clone()
             .into_par_iter()
-            .filter(|file| !do_check(&format_options, file))
+            .filter(|file| !do_check(opt, &format_options, file))
             .collect::<Vec<_>>()
     } else {
         opt.files
             .iter()
-            .filter(|file| !do_check(&format_options, file))
+            .filter(|file| !do_check(opt, &format_options, file))
             .cloned()
             .collect::<Vec<_>>()
     };
@@ -298,11 +490,34 @@ fn validate_formatted_text<P: AsRef<Path>>(
     path: P,
     original: &str,
     formatted: &str,
+    reflow_comments: bool,
 ) -> anyhow::Result<()> {
     use erl_tokenize::{PositionRange as _, Result, Token, Tokenizer};
 
-    fn is_visible_token(t: &Result<Token>) -> bool {
-        !matches!(t, Ok(Token::Whitespace(_)))
+    // If comments are reflowed, they are compared separately by their word sequences.
+    let is_visible_token = |t: &Result<Token>| match t {
+        Ok(Token::Whitespace(_)) => false,
+        Ok(Token::Comment(_)) => !reflow_comments,
+        _ => true,
+    };
+    fn comment_words<'a>(text: &'a str, path: &Path) -> Vec<&'a str> {
+        Tokenizer::new(&*efmt::parse::mask_unsupported_syntax(text, Some(path)))
+            .filter_map(|t| match t {
+                Ok(Token::Comment(x)) => Some(x),
+                _ => None,
+            })
+            .flat_map(|x| {
+                let text = &text[x.start_position().offset()..x.end_position().offset()];
+                text.trim_start_matches('%').split_whitespace()
+            })
+            .collect()
+    }
+    if reflow_comments {
+        anyhow::ensure!(
+            comment_words(original, path.as_ref()) == comment_words(formatted, path.as_ref()),
+            "The words in the comments of {:?} were changed",
+            path.as_ref()
+        );
     }
 
     fn check_extra_token<P: AsRef<Path>>(
@@ -323,22 +538,48 @@ fn validate_formatted_text<P: AsRef<Path>>(
         Ok(())
     }
 
-    fn text(token: &Token) -> &str {
-        if let Token::Comment(token) = token {
-            token.text().trim_end()
+    // Triple-quoted strings and sigils are masked for tokenization, so token texts are taken from the unmasked source.
+    fn text<'a>(source: &'a str, token: &Token) -> &'a str {
+        let text = &source[token.start_position().offset()..token.end_position().offset()];
+        if let Token::Comment(_) = token {
+            text.trim_end()
         } else {
-            token.text()
+            text
         }
     }
 
-    let mut tokens0 = Tokenizer::new(original).filter(is_visible_token);
-    let mut tokens1 = Tokenizer::new(formatted).filter(is_visible_token);
-    while let Some(t0) = tokens0.next().transpose().expect("unreachable") {
+    let masked_original = efmt::parse::mask_unsupported_syntax(original, Some(path.as_ref()));
+    let masked_formatted = efmt::parse::mask_unsupported_syntax(formatted, Some(path.as_ref()));
+    let tokens0 = Tokenizer::new(&*masked_original).filter(is_visible_token);
+    let mut tokens1 = Tokenizer::new(&*masked_formatted).filter(is_visible_token);
+    for t0 in tokens0 {
+        let t0 = match t0 {
+            Ok(t0) => t0,
+            Err(e) => {
+                // The rest of the text that could not be tokenized is kept as it is (see `--allow-partial`).
+                let rest = &original[e.position().offset()..];
+                let is_kept = matches!(
+                    tokens1.next(),
+                    Some(Err(e)) if &formatted[e.position().offset()..] == rest
+                );
+                anyhow::ensure!(
+                    is_kept,
+                    "{}",
+                    efmt::error::generate_error_message(
+                        original,
+                        Some(path),
+                        e.position().clone().into(),
+                        "the text after this position was changed"
+                    )
+                );
+                return Ok(());
+            }
+        };
         let t1 = match tokens1.next() {
             Some(Ok(t1)) => t1,
             Some(Err(e)) => {
                 let reason = e.to_string();
-                let reason_end = reason.find(" (").unwrap_or_else(|| reason.len());
+                let reason_end = reason.find(" (").unwrap_or(reason.len());
                 anyhow::bail!(
                     "{}",
                     efmt::error::generate_error_message(
@@ -354,7 +595,7 @@ fn validate_formatted_text<P: AsRef<Path>>(
             }
         };
         anyhow::ensure!(
-            text(&t0) == text(&t1),
+            text(original, &t0) == text(formatted, &t1),
             "{}\n{}",
             efmt::error::generate_error_message(
                 original,
@@ -0,0 +1,150 @@
+//! Include files and macros that could not be resolved during formatting.
+use crate::span::Position;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Include files and macros that could not be resolved in a text.
+///
+/// Each value is the position where the item is first used.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Missing {
+    /// The paths of `-include` and `-include_lib` directives that could not be resolved.
+    pub includes: BTreeMap<String, Position>,
+
+    /// The names of the macros that are not defined (they are replaced with `EFMT_DUMMY` atom).
+    pub macros: BTreeMap<String, Position>,
+}
+
+impl Missing {
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty() && self.macros.is_empty()
+    }
+
+    /// Generates an error message for each missing item in `text`.
+    pub fn error_messages(&self, text: &str, path: Option<&Path>) -> Vec<String> {
+        let includes = self.includes.iter().map(|(include, position)| {
+            let reason = format!("unresolved include file {:?}", include);
+            crate::error::generate_error_message(text, path, *position, &reason)
+        });
+        let macros = self.macros.iter().map(|(name, position)| {
+            let reason = format!("undefined macro `?{}`", name);
+            crate::error::generate_error_message(text, path, *position, &reason)
+        });
+        includes.chain(macros).collect()
+    }
+}
+
+/// A summary of the missing items in multiple files (see [crate::Options::report_missing]).
+///
+/// This is a shared handle, so the clones of an instance collect the items into the same summary.
+#[derive(Debug, Default, Clone)]
+pub struct MissingReport {
+    inner: Arc<Mutex<MissingReportInner>>,
+}
+
+#[derive(Debug, Default)]
+struct MissingReportInner {
+    includes: BTreeMap<String, BTreeSet<PathBuf>>,
+    macros: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+impl MissingReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the missing items in the file located at `path`.
+    pub fn add(&self, path: Option<&Path>, missing: &Missing) {
+        let path = path.unwrap_or_else(|| Path::new("<unknown>"));
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        for include in missing.includes.keys() {
+            inner
+                .includes
+                .entry(include.clone())
+                .or_default()
+                .insert(path.to_path_buf());
+        }
+        for name in missing.macros.keys() {
+            inner
+                .macros
+                .entry(name.clone())
+                .or_default()
+                .insert(path.to_path_buf());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.includes.is_empty() && inner.macros.is_empty()
+    }
+}
+
+impl std::fmt::Display for MissingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let sections = [
+            ("Unresolved include files", &inner.includes, ""),
+            ("Undefined macros", &inner.macros, "?"),
+        ];
+        let mut is_first = true;
+        for (title, items, prefix) in sections {
+            if items.is_empty() {
+                continue;
+            }
+            if !is_first {
+                writeln!(f)?;
+            }
+            is_first = false;
+
+            writeln!(f, "{} ({}):", title, items.len())?;
+            for (item, paths) in items {
+                writeln!(f, "- {}{}", prefix, item)?;
+                for path in paths {
+                    writeln!(f, "  - {}", path.to_str().unwrap_or("<unknown>"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_works() {
+        let mut missing = Missing::default();
+        missing
+            .includes
+            .insert("foo.hrl".to_owned(), Position::new(0, 1, 1));
+        missing
+            .macros
+            .insert("BAR".to_owned(), Position::new(0, 1, 1));
+        missing
+            .macros
+            .insert("BAZ".to_owned(), Position::new(0, 1, 1));
+
+        let report = MissingReport::new();
+        assert!(report.is_empty());
+        report.clone().add(Some(Path::new("b.erl")), &missing);
+        missing.includes.clear();
+        missing.macros.remove("BAZ");
+        report.add(Some(Path::new("a.erl")), &missing);
+        assert!(!report.is_empty());
+
+        let expected = r#"Unresolved include files (1):
+- foo.hrl
+  - b.erl
+
+Undefined macros (2):
+- ?BAR
+  - a.erl
+  - b.erl
+- ?BAZ
+  - b.erl
+"#;
+        similar_asserts::assert_str_eq!(report.to_string(), expected);
+    }
+}
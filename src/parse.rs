@@ -1,6 +1,7 @@
 use crate::items::tokens::LexicalToken;
 use crate::span::{Position, Span as _};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub use self::include::IncludeOptions;
@@ -9,30 +10,64 @@ pub use self::token_stream::TokenStream;
 /// A procedural macro to derive [Parse].
 pub use efmt_derive::Parse;
 
+pub(crate) mod condition;
 pub(crate) mod include;
+pub(crate) mod template;
+pub(crate) mod token_buffer;
 pub(crate) mod token_stream;
+pub(crate) mod verbatim;
+
+/// Replaces the syntax that `erl_tokenize` doesn't support with tokenizable text of the same length.
+///
+/// - Triple-quoted strings and sigils are replaced with plain strings.
+/// - The escript shebang line (e.g., `#!/usr/bin/env escript`) is replaced with a comment.
+/// - `${VAR}` placeholders in `*.config.src` templates are replaced with variables.
+///
+/// As the positions of the tokens are unchanged, their texts can be taken from the original text.
+pub fn mask_unsupported_syntax<'a>(text: &'a str, path: Option<&Path>) -> Cow<'a, str> {
+    let mut masked = self::verbatim::mask_verbatim_literals(text);
+    if path.is_some_and(self::template::is_template) {
+        if let Cow::Owned(x) = self::template::mask_placeholders(&masked) {
+            masked = Cow::Owned(x);
+        }
+    }
+    if masked.starts_with("#!") {
+        masked.to_mut().replace_range(..1, "%");
+    }
+    masked
+}
 
 /// Possible errors.
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
     /// Unexpected EOF.
-    #[error("Parse failed:{}", Self::unexpected_eof_message(.position, .text, .path))]
+    #[error("Parse failed [{}]:{}", self.code(), Self::unexpected_eof_message(.position, .text, .path, .expected))]
     UnexpectedEof {
         position: Position,
         text: Arc<String>,
         path: Option<Arc<PathBuf>>,
+        expected: Vec<Expected>,
     },
 
     /// Unexpected token.
-    #[error("Parse failed:{}", Self::unexpected_token_message(.position, .text, .path))]
+    #[error("Parse failed [{}]:{}", self.code(), Self::unexpected_token_message(.position, .text, .path, .expected, .expansions))]
     UnexpectedToken {
         position: Position,
         text: Arc<String>,
         path: Option<Arc<PathBuf>>,
+        expected: Vec<Expected>,
+
+        /// The macro expansions that produced the token (the innermost first).
+        expansions: Vec<ExpansionNote>,
+
+        /// The index of the token in [TokenStream].
+        ///
+        /// This is used to compare errors in a macro expansion as the expanded tokens have the same position.
+        token_index: usize,
     },
 
     /// Error during tokenization.
-    #[error("Tokenize failed:{}", Self::tokenize_error_message(.source, .text))]
+    #[error("Tokenize failed [{}]:{}", self.code(), Self::tokenize_error_message(.source, .text))]
     TokenizeError {
         source: erl_tokenize::Error,
         text: Arc<String>,
@@ -40,11 +75,19 @@ pub enum Error {
 }
 
 impl Error {
-    pub(crate) fn unexpected_token(ts: &TokenStream, token: LexicalToken) -> Self {
+    /// Makes an error for `token` at `token_index` (see [TokenStream::last_token_index]).
+    pub(crate) fn unexpected_token(
+        ts: &TokenStream,
+        token: LexicalToken,
+        token_index: usize,
+    ) -> Self {
         Self::UnexpectedToken {
             position: token.start_position(),
             text: ts.text(),
             path: ts.filepath(),
+            expected: Vec::new(),
+            expansions: ts.expansion_notes(token_index),
+            token_index,
         }
     }
 
@@ -53,6 +96,7 @@ impl Error {
             position: ts.prev_token_end_position(),
             text: ts.text(),
             path: ts.filepath(),
+            expected: Vec::new(),
         }
     }
 
@@ -63,6 +107,62 @@ impl Error {
         }
     }
 
+    /// Adds a token kind that was expected at the error position.
+    pub(crate) fn expected(mut self, kind: Expected) -> Self {
+        if let Self::UnexpectedEof { expected, .. } | Self::UnexpectedToken { expected, .. } =
+            &mut self
+        {
+            if !expected.contains(&kind) {
+                expected.push(kind);
+            }
+        }
+        self
+    }
+
+    /// Merges the expected token kinds of `other` that failed at the same position.
+    pub(crate) fn merge_expected(mut self, other: &Self) -> Self {
+        for kind in other.expected_kinds() {
+            self = self.expected(*kind);
+        }
+        self
+    }
+
+    /// Returns the stable code of this error (e.g., `E0002`).
+    ///
+    /// | Code    | Error            |
+    /// |---------|------------------|
+    /// | `E0001` | unexpected EOF   |
+    /// | `E0002` | unexpected token |
+    /// | `E0003` | tokenize error   |
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnexpectedEof { .. } => "E0001",
+            Self::UnexpectedToken { .. } => "E0002",
+            Self::TokenizeError { .. } => "E0003",
+        }
+    }
+
+    /// Returns the token kinds that were expected at the error position.
+    pub fn expected_kinds(&self) -> &[Expected] {
+        match self {
+            Self::UnexpectedEof { expected, .. } | Self::UnexpectedToken { expected, .. } => {
+                expected
+            }
+            Self::TokenizeError { .. } => &[],
+        }
+    }
+
+    // Returns a key to determine which error occurred later.
+    //
+    // Unexpected tokens are compared by their indices as the tokens in a macro expansion have the same position.
+    // The other errors always occur after all of the tokens are read.
+    pub(crate) fn progress(&self) -> (bool, usize) {
+        match self {
+            Self::UnexpectedToken { token_index, .. } => (false, *token_index),
+            _ => (true, self.position().offset()),
+        }
+    }
+
     pub(crate) fn position(&self) -> Position {
         match self {
             Self::UnexpectedEof { position, .. } => *position,
@@ -73,9 +173,7 @@ impl Error {
 
     fn tokenize_error_message(source: &erl_tokenize::Error, text: &Arc<String>) -> String {
         let source_message = source.to_string();
-        let source_message_end = source_message
-            .find(" (")
-            .unwrap_or_else(|| source_message.len());
+        let source_message_end = source_message.find(" (").unwrap_or(source_message.len());
         crate::error::generate_error_message(
             text,
             source.position().filepath(),
@@ -88,12 +186,13 @@ impl Error {
         position: &Position,
         text: &Arc<String>,
         path: &Option<Arc<PathBuf>>,
+        expected: &[Expected],
     ) -> String {
         crate::error::generate_error_message(
             text,
             path.as_ref().map(|x| &**x),
             *position,
-            "unexpected EOF",
+            &Self::reason("unexpected EOF", expected),
         )
     }
 
@@ -101,14 +200,118 @@ impl Error {
         position: &Position,
         text: &Arc<String>,
         path: &Option<Arc<PathBuf>>,
+        expected: &[Expected],
+        expansions: &[ExpansionNote],
     ) -> String {
-        crate::error::generate_error_message(
+        crate::error::generate_error_message_with_notes(
             text,
             path.as_ref().map(|x| &**x),
             *position,
-            "unexpected token",
+            &Self::reason("unexpected token", expected),
+            &expansions.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
         )
     }
+
+    fn reason(reason: &str, expected: &[Expected]) -> String {
+        match expected {
+            [] => reason.to_owned(),
+            [kind] => format!("{reason}, expected {kind}"),
+            kinds => {
+                let kinds = kinds.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                format!("{reason}, expected one of {}", kinds.join(", "))
+            }
+        }
+    }
+}
+
+/// A token kind that was expected at the position of an [Error].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expected {
+    /// A specific token (e.g., `,` or `end`).
+    Token(&'static str),
+
+    /// A kind of tokens or items (e.g., `atom` or `expression`).
+    Kind(&'static str),
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Token(x) => write!(f, "`{x}`"),
+            Self::Kind(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+/// A macro expansion that produced the token of an [Error].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionNote {
+    macro_name: String,
+    defined_at: Option<(Option<PathBuf>, usize)>,
+}
+
+impl ExpansionNote {
+    pub(crate) fn new(macro_name: String, defined_at: Option<(Option<PathBuf>, usize)>) -> Self {
+        Self {
+            macro_name,
+            defined_at,
+        }
+    }
+
+    /// Returns the name of the expanded macro.
+    pub fn macro_name(&self) -> &str {
+        &self.macro_name
+    }
+
+    /// Returns the file path and the line number of the `-define` directive of the macro.
+    ///
+    /// `None` means that the macro is predefined (e.g., `?MODULE`) or undefined.
+    pub fn defined_at(&self) -> Option<(Option<&Path>, usize)> {
+        self.defined_at
+            .as_ref()
+            .map(|(path, line)| (path.as_deref(), *line))
+    }
+}
+
+impl std::fmt::Display for ExpansionNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "in expansion of ?{}", self.macro_name)?;
+        if let Some((path, line)) = self.defined_at() {
+            // Paths under the current directory are shown as relative paths.
+            let current_dir = std::env::current_dir().ok();
+            let path = path.map(|path| {
+                current_dir
+                    .as_ref()
+                    .and_then(|dir| path.strip_prefix(dir).ok())
+                    .unwrap_or(path)
+            });
+            let file = path.and_then(|x| x.to_str()).unwrap_or("<unknown>");
+            write!(f, " (defined at {file}:{line})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors found in a text.
+///
+/// Multiple errors are reported if the parser could recover from them
+/// (i.e., skip the forms containing the errors and resume parsing from the next form).
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{}", Self::message(.0))]
+pub struct Errors(pub Vec<Error>);
+
+impl Errors {
+    fn message(errors: &[Error]) -> String {
+        let mut message = errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if errors.len() > 1 {
+            message.push_str(&format!("\n\nFound {} errors", errors.len()));
+        }
+        message
+    }
 }
 
 /// A specialized [Result][std::result::Result] type for this module.
@@ -163,10 +366,10 @@ mod tests {
         similar_asserts::assert_str_eq!(
             err.to_string(),
             indoc::indoc! {"
-        Parse failed:
+        Parse failed [E0002]:
         --> <unknown>:2:15
         2 |     [a, b | #c].
-          |               ^ unexpected token"}
+          |               ^ unexpected token, expected one of `{`, `.`"}
         );
     }
 
@@ -181,10 +384,31 @@ mod tests {
         similar_asserts::assert_str_eq!(
             err.to_string(),
             indoc::indoc! {"
-        Parse failed:
+        Parse failed [E0002]:
         --> <unknown>:3:19
         3 |     ?ID([a, b | #c]).
-          |                   ^ unexpected token"}
+          |                   ^ unexpected token, expected one of `{`, `.`"}
+        );
+    }
+
+    #[test]
+    fn unexpected_token_message_in_macro_expansion_works() {
+        let text = indoc::indoc! {"
+        -define(BAD, (a b)).
+        -define(WRAP(X), [X, ?BAD]).
+        foo() ->
+            ?WRAP(1).
+        "};
+        let err = crate::format_text::<Module>(text).err().unwrap();
+        similar_asserts::assert_str_eq!(
+            err.to_string(),
+            indoc::indoc! {"
+        Parse failed [E0002]:
+        --> <unknown>:4:5
+        4 |     ?WRAP(1).
+          |     ^ unexpected token, expected `)`
+          = note: in expansion of ?BAD (defined at <unknown>:1)
+          = note: in expansion of ?WRAP (defined at <unknown>:2)"}
         );
     }
 
@@ -198,10 +422,39 @@ mod tests {
         similar_asserts::assert_str_eq!(
             err.to_string(),
             indoc::indoc! {"
-        Parse failed:
+        Parse failed [E0001]:
         --> <unknown>:2:10
         2 |     hello
-          |          ^ unexpected EOF"}
+          |          ^ unexpected EOF, expected one of `,`, `;`, `.`"}
+        );
+    }
+
+    #[test]
+    fn multiple_errors_message_works() {
+        let text = indoc::indoc! {"
+        -module(foo).
+        foo(A) ->
+            bar(A b).
+
+        baz() -> ok.
+
+        qux() -> [1, 2 | ].
+        "};
+        let err = crate::format_text::<Module>(text).err().unwrap();
+        similar_asserts::assert_str_eq!(
+            err.to_string(),
+            indoc::indoc! {"
+        Parse failed [E0002]:
+        --> <unknown>:3:11
+        3 |     bar(A b).
+          |           ^ unexpected token, expected one of `,`, `)`
+
+        Parse failed [E0002]:
+        --> <unknown>:7:18
+        7 | qux() -> [1, 2 | ].
+          |                  ^ unexpected token, expected expression
+
+        Found 2 errors"}
         );
     }
 
@@ -215,7 +468,7 @@ mod tests {
         similar_asserts::assert_str_eq!(
             err.to_string(),
             indoc::indoc! {r#"
-        Tokenize failed:
+        Tokenize failed [E0003]:
         --> <unknown>:2:5
         2 |     "hello
           |     ^ no closing quotation"#}
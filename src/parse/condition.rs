@@ -0,0 +1,241 @@
+//! Evaluation of the conditions of `-if` and `-elif` directives.
+//!
+//! Only the expressions that can be evaluated without a runtime are supported,
+//! i.e., integer and atom literals combined with arithmetic, comparison and boolean operators
+//! (e.g., `?OTP_RELEASE >= 26 andalso ?FEATURE_ENABLED(maybe_expr)`).
+use crate::items::tokens::LexicalToken;
+use erl_tokenize::values::{Keyword, Symbol};
+use std::cmp::Ordering;
+
+/// Evaluates a condition consisting of `tokens` (the macros have already been expanded).
+///
+/// Returns `None` if the condition is not supported or doesn't evaluate to a boolean.
+pub(crate) fn evaluate(tokens: &[LexicalToken]) -> Option<bool> {
+    let mut evaluator = Evaluator { tokens, index: 0 };
+    let value = evaluator.orelse()?;
+    if evaluator.index != tokens.len() {
+        return None;
+    }
+    value.as_bool()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Integer(i128),
+    Atom(String),
+}
+
+impl Value {
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Atom(x) if x == "true" => Some(true),
+            Self::Atom(x) if x == "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn as_integer(&self) -> Option<i128> {
+        match self {
+            Self::Integer(x) => Some(*x),
+            Self::Atom(_) => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(x: bool) -> Self {
+        Self::Atom(x.to_string())
+    }
+}
+
+struct Evaluator<'a> {
+    tokens: &'a [LexicalToken],
+    index: usize,
+}
+
+impl Evaluator<'_> {
+    fn orelse(&mut self) -> Option<Value> {
+        let left = self.andalso()?;
+        if self.read_keyword(Keyword::Orelse) {
+            let right = self.orelse()?;
+            return Some((left.as_bool()? || right.as_bool()?).into());
+        }
+        Some(left)
+    }
+
+    fn andalso(&mut self) -> Option<Value> {
+        let left = self.comparison()?;
+        if self.read_keyword(Keyword::Andalso) {
+            let right = self.andalso()?;
+            return Some((left.as_bool()? && right.as_bool()?).into());
+        }
+        Some(left)
+    }
+
+    fn comparison(&mut self) -> Option<Value> {
+        let left = self.additive()?;
+        let Some(LexicalToken::Symbol(op)) = self.tokens.get(self.index) else {
+            return Some(left);
+        };
+        let op = op.value();
+        if !matches!(
+            op,
+            Symbol::Eq
+                | Symbol::ExactEq
+                | Symbol::NotEq
+                | Symbol::ExactNotEq
+                | Symbol::Less
+                | Symbol::LessEq
+                | Symbol::Greater
+                | Symbol::GreaterEq
+        ) {
+            return Some(left);
+        }
+        self.index += 1;
+        let right = self.additive()?;
+
+        let ordering = match (&left, &right) {
+            (Value::Integer(x), Value::Integer(y)) => x.cmp(y),
+            _ if matches!(op, Symbol::Eq | Symbol::ExactEq) => return Some((left == right).into()),
+            _ if matches!(op, Symbol::NotEq | Symbol::ExactNotEq) => {
+                return Some((left != right).into())
+            }
+            _ => return None,
+        };
+        let value = match op {
+            Symbol::Eq | Symbol::ExactEq => ordering == Ordering::Equal,
+            Symbol::NotEq | Symbol::ExactNotEq => ordering != Ordering::Equal,
+            Symbol::Less => ordering == Ordering::Less,
+            Symbol::LessEq => ordering != Ordering::Greater,
+            Symbol::Greater => ordering == Ordering::Greater,
+            _ => ordering != Ordering::Less,
+        };
+        Some(value.into())
+    }
+
+    fn additive(&mut self) -> Option<Value> {
+        let mut left = self.multiplicative()?;
+        loop {
+            if self.read_symbol(Symbol::Plus) {
+                let right = self.multiplicative()?;
+                left = Value::Integer(left.as_integer()?.checked_add(right.as_integer()?)?);
+            } else if self.read_symbol(Symbol::Hyphen) {
+                let right = self.multiplicative()?;
+                left = Value::Integer(left.as_integer()?.checked_sub(right.as_integer()?)?);
+            } else if self.read_keyword(Keyword::Or) {
+                let right = self.multiplicative()?;
+                left = (left.as_bool()? || right.as_bool()?).into();
+            } else if self.read_keyword(Keyword::Xor) {
+                let right = self.multiplicative()?;
+                left = (left.as_bool()? != right.as_bool()?).into();
+            } else {
+                return Some(left);
+            }
+        }
+    }
+
+    fn multiplicative(&mut self) -> Option<Value> {
+        let mut left = self.unary()?;
+        loop {
+            if self.read_symbol(Symbol::Multiply) {
+                let right = self.unary()?;
+                left = Value::Integer(left.as_integer()?.checked_mul(right.as_integer()?)?);
+            } else if self.read_keyword(Keyword::Div) {
+                let right = self.unary()?;
+                left = Value::Integer(left.as_integer()?.checked_div(right.as_integer()?)?);
+            } else if self.read_keyword(Keyword::Rem) {
+                let right = self.unary()?;
+                left = Value::Integer(left.as_integer()?.checked_rem(right.as_integer()?)?);
+            } else if self.read_keyword(Keyword::And) {
+                let right = self.unary()?;
+                left = (left.as_bool()? && right.as_bool()?).into();
+            } else {
+                return Some(left);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<Value> {
+        if self.read_keyword(Keyword::Not) {
+            Some((!self.unary()?.as_bool()?).into())
+        } else if self.read_symbol(Symbol::Hyphen) {
+            Some(Value::Integer(self.unary()?.as_integer()?.checked_neg()?))
+        } else if self.read_symbol(Symbol::Plus) {
+            Some(Value::Integer(self.unary()?.as_integer()?))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Option<Value> {
+        let value = match self.tokens.get(self.index)? {
+            LexicalToken::Integer(x) => Value::Integer(x.value().parse().ok()?),
+            LexicalToken::Atom(x) => Value::Atom(x.value().to_owned()),
+            LexicalToken::Symbol(x) if x.value() == Symbol::OpenParen => {
+                self.index += 1;
+                let value = self.orelse()?;
+                if !self.read_symbol(Symbol::CloseParen) {
+                    return None;
+                }
+                return Some(value);
+            }
+            _ => return None,
+        };
+        self.index += 1;
+        Some(value)
+    }
+
+    fn read_symbol(&mut self, symbol: Symbol) -> bool {
+        let found = matches!(
+            self.tokens.get(self.index),
+            Some(LexicalToken::Symbol(x)) if x.value() == symbol
+        );
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn read_keyword(&mut self, keyword: Keyword) -> bool {
+        let found = matches!(
+            self.tokens.get(self.index),
+            Some(LexicalToken::Keyword(x)) if x.value() == keyword
+        );
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use erl_tokenize::Tokenizer;
+
+    fn eval(text: &str) -> Option<bool> {
+        let mut ts = crate::parse::TokenStream::new(
+            Tokenizer::new(text.to_owned()),
+            crate::parse::IncludeOptions::new().disable_include(),
+        );
+        let tokens = ts
+            .by_ref()
+            .collect::<crate::parse::Result<Vec<_>>>()
+            .unwrap();
+        evaluate(&tokens)
+    }
+
+    #[test]
+    fn evaluate_works() {
+        assert_eq!(eval("?OTP_RELEASE >= 26"), Some(true));
+        assert_eq!(eval("?OTP_RELEASE < 16#1A"), Some(false));
+        assert_eq!(
+            eval("(1 + 2) * 3 =:= 9 andalso not (foo == bar)"),
+            Some(true)
+        );
+        assert_eq!(eval("false orelse 10 div 3 - 1 =/= 2"), Some(false));
+        assert_eq!(eval("?OTP_RELEASE"), None);
+        assert_eq!(eval("foo > 1"), None);
+        assert_eq!(eval("is_atom(foo)"), None);
+    }
+}
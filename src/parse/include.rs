@@ -2,12 +2,13 @@ use crate::items::forms::IncludeDirective;
 use crate::items::Module;
 use crate::parse::token_stream::{MacroDefine, MacroDefineKey, MacroDefines};
 use crate::parse::TokenStream;
+use crate::span::{Position, Span as _};
 use erl_tokenize::Tokenizer;
 use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-const CACHE_FORMAT_VERISON: &str = "v0";
+const CACHE_FORMAT_VERISON: &str = "v1";
 
 #[derive(Debug, Default, Clone)]
 pub struct IncludeOptions {
@@ -41,6 +42,7 @@ impl IncludeOptions {
 pub struct IncludeHandler {
     options: IncludeOptions,
     included: HashSet<PathBuf>,
+    unresolved: BTreeMap<String, Position>,
 }
 
 impl IncludeHandler {
@@ -48,9 +50,15 @@ impl IncludeHandler {
         Self {
             options,
             included: HashSet::new(),
+            unresolved: BTreeMap::new(),
         }
     }
 
+    /// Returns the paths of the include directives that could not be resolved and their positions.
+    pub fn unresolved(&self) -> &BTreeMap<String, Position> {
+        &self.unresolved
+    }
+
     fn cache_path(&self, include: &IncludeDirective) -> Option<PathBuf> {
         let cache_root_dir = if let Some(dir) = &self.options.include_cache_dir {
             dir
@@ -188,6 +196,9 @@ impl IncludeHandler {
                     "Failed to resolve the include file path {:?}",
                     include.path()
                 );
+                self.unresolved
+                    .entry(include.path().to_owned())
+                    .or_insert_with(|| include.start_position());
                 return None;
             };
         log::debug!(
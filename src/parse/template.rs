@@ -0,0 +1,82 @@
+//! `${VAR}` placeholders in `*.config.src` templates (e.g., `{port, ${PORT}}`).
+//!
+//! The placeholders are replaced with variables of the same length (e.g., `__PORT_`) before tokenization
+//! so that the template can be parsed as a config file.
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Returns `true` if `path` is a template that may contain `${VAR}` placeholders.
+pub(crate) fn is_template(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.ends_with(".config.src"))
+}
+
+/// Replaces the `${VAR}` placeholders outside of strings, quoted atoms and comments with variables.
+pub(crate) fn mask_placeholders(text: &str) -> Cow<'_, str> {
+    let bytes = text.as_bytes();
+    let mut masked: Option<Vec<u8>> = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                i = text[i..].find('\n').map_or(bytes.len(), |n| i + n);
+            }
+            b'"' | b'\'' => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+            }
+            b'$' => {
+                if let Some(len) = placeholder_len(&bytes[i..]) {
+                    let masked = masked.get_or_insert_with(|| bytes.to_vec());
+                    masked[i] = b'_';
+                    masked[i + 1] = b'_';
+                    masked[i + len - 1] = b'_';
+                    i += len;
+                } else {
+                    // A character literal such as `$"` or `$\'`.
+                    i += if bytes.get(i + 1) == Some(&b'\\') {
+                        3
+                    } else {
+                        2
+                    };
+                }
+            }
+            _ => {
+                i += 1;
+            }
+        }
+    }
+    match masked {
+        None => Cow::Borrowed(text),
+        Some(masked) => Cow::Owned(String::from_utf8(masked).expect("unreachable")),
+    }
+}
+
+// Returns the length of `${VAR}` at the start of `bytes`.
+fn placeholder_len(bytes: &[u8]) -> Option<usize> {
+    let name = bytes.strip_prefix(b"${")?;
+    let name_len = name
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+        .count();
+    (name_len > 0 && name.get(name_len) == Some(&b'}')).then_some(name_len + 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_placeholders_works() {
+        let text = r#"[{a, ${A}}, {"${B}", '${C}', $$, ${D_1}}]. % ${E}"#;
+        assert_eq!(
+            mask_placeholders(text),
+            r#"[{a, __A_}, {"${B}", '${C}', $$, __D_1_}]. % ${E}"#
+        );
+    }
+}
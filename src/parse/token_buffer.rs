@@ -0,0 +1,112 @@
+//! A gap buffer of tokens used by [TokenStream][crate::parse::TokenStream].
+use std::ops::{Index, Range};
+
+/// A sequence of tokens that supports replacing a range near the last replaced position cheaply.
+///
+/// Macro expansions replace the tokens of a macro call with the expanded tokens.
+/// Unlike [Vec::splice], the tokens after the replaced range are not moved;
+/// only the tokens between the previous and the current replacement positions are.
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBuffer<T> {
+    front: Vec<T>,
+
+    // The tokens after the gap in reverse order.
+    back: Vec<T>,
+}
+
+impl<T> TokenBuffer<T> {
+    pub fn new() -> Self {
+        Self {
+            front: Vec::new(),
+            back: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if let Some(x) = self.front.get(index) {
+            Some(x)
+        } else {
+            let i = index - self.front.len();
+            self.back.len().checked_sub(i + 1).map(|i| &self.back[i])
+        }
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.back.first().or_else(|| self.front.last())
+    }
+
+    pub fn push(&mut self, token: T) {
+        self.move_gap(self.len());
+        self.front.push(token);
+    }
+
+    /// Replaces the tokens in `range` with `tokens`.
+    pub fn replace<I>(&mut self, range: Range<usize>, tokens: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.move_gap(range.end);
+        self.front.truncate(range.start);
+        self.front.extend(tokens);
+    }
+
+    #[cfg(test)]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.front.iter().chain(self.back.iter().rev())
+    }
+
+    fn move_gap(&mut self, index: usize) {
+        while self.front.len() > index {
+            self.back.push(self.front.pop().expect("unreachable"));
+        }
+        while self.front.len() < index {
+            self.front.push(self.back.pop().expect("out of range"));
+        }
+    }
+}
+
+impl<T> FromIterator<T> for TokenBuffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            front: iter.into_iter().collect(),
+            back: Vec::new(),
+        }
+    }
+}
+
+impl<T> Index<usize> for TokenBuffer<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("out of range")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_buffer_works() {
+        let mut buf = (0..6).collect::<TokenBuffer<_>>();
+        buf.replace(4..5, [40, 41]);
+        buf.replace(1..3, []);
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [0, 3, 40, 41, 5]);
+        assert_eq!(
+            (buf.len(), buf[1], buf.get(4), buf.get(5)),
+            (5, 3, Some(&5), None)
+        );
+        assert_eq!(buf.last(), Some(&5));
+
+        buf.push(6);
+        buf.replace(0..1, [-1]);
+        assert_eq!(
+            buf.iter().copied().collect::<Vec<_>>(),
+            [-1, 3, 40, 41, 5, 6]
+        );
+    }
+}
@@ -1,65 +1,231 @@
 use crate::items::components::Either;
-use crate::items::forms::{DefineDirective, IncludeDirective};
-use crate::items::macros::{Macro, MacroName};
+use crate::items::forms::{
+    ConditionalDirective, ConditionalKind, DefineDirective, FeatureAttr, IncludeDirective,
+    ModuleAttr,
+};
+use crate::items::macros::{ExpandedToken, Macro, MacroName};
 use crate::items::symbols::{OpenParenSymbol, QuestionSymbol};
 use crate::items::tokens::{
     AtomToken, CharToken, CommentKind, CommentToken, FloatToken, IntegerToken, KeywordToken,
     LexicalToken, StringToken, SymbolToken, VariableToken,
 };
+use crate::missing::Missing;
 use crate::parse::include::IncludeHandler;
-use crate::parse::{Error, IncludeOptions, Parse, Result, ResumeParse};
+use crate::parse::token_buffer::TokenBuffer;
+use crate::parse::{Error, ExpansionNote, IncludeOptions, Parse, Result, ResumeParse};
 use crate::span::{Position, Span as _};
 use erl_tokenize::values::Symbol;
 use erl_tokenize::{PositionRange as _, Tokenizer};
-use std::collections::{BTreeMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// The maximum depth of nested macro expansions.
+///
+/// Expansions deeper than this are treated in the same way as circular macros.
+const MAX_MACRO_EXPANSION_DEPTH: usize = 256;
+
 #[derive(Debug)]
 pub struct TokenStream {
     tokenizer: Tokenizer<String>,
-    tokens: Vec<LexicalToken>,
+    tokens: TokenBuffer<StreamToken>,
     current_token_index: usize,
+    last_token_index: usize,
     comments: BTreeMap<Position, CommentToken>,
     macros: BTreeMap<Position, Macro>,
     macro_defines: MacroDefines,
     new_macro_defines: HashSet<MacroDefineKey>,
-    missing_macros: HashSet<String>,
-    known_replacement: HashSet<(usize, Vec<LexicalToken>)>,
+    missing_macros: BTreeMap<String, Position>,
+    expansions: Vec<Expansion>,
+    expansion_indices: HashMap<Expansion, usize>,
     disable_macro_expand: bool,
     parsing_tokens: bool,
     text: Arc<String>,
     path: Option<Arc<PathBuf>>,
     last_parse_error: Option<Error>,
     include: IncludeHandler,
+    module_name: Option<String>,
+    current_function: Option<String>,
+    current_function_arity: Option<usize>,
+    conditionals: Vec<Conditional>,
+    otp_release: u32,
+    features: BTreeMap<String, bool>,
+    verbatim_literals: HashSet<usize>,
+    allow_partial: bool,
+    recover_errors: bool,
+    recovered_errors: Vec<Error>,
+    is_tokenizer_stopped: bool,
 }
 
 impl TokenStream {
-    pub fn new(tokenizer: Tokenizer<String>, options: IncludeOptions) -> Self {
+    pub fn new(mut tokenizer: Tokenizer<String>, options: IncludeOptions) -> Self {
         let text = Arc::new(tokenizer.text().to_owned());
         let path = tokenizer
             .next_position()
             .filepath()
             .map(|p| Arc::new(p.to_owned()));
+
+        // Triple-quoted strings and sigils are tokenized as plain strings and restored in `read_token()`.
+        // Other masked syntax (see `mask_unsupported_syntax()`) is restored when the tokens are written.
+        let verbatim_literals = crate::parse::verbatim::find_verbatim_literals(&text)
+            .into_iter()
+            .map(|x| x.start)
+            .collect::<HashSet<_>>();
+        if let Cow::Owned(masked) =
+            crate::parse::mask_unsupported_syntax(&text, path.as_deref().map(|x| x.as_path()))
+        {
+            tokenizer = Tokenizer::new(masked);
+            if let Some(path) = &path {
+                tokenizer.set_filepath(path.as_path());
+            }
+        }
+
         Self {
             tokenizer,
-            tokens: Vec::new(),
+            tokens: TokenBuffer::new(),
             current_token_index: 0,
+            last_token_index: 0,
             comments: BTreeMap::new(),
             macros: BTreeMap::new(),
             macro_defines: BTreeMap::new(),
             new_macro_defines: HashSet::new(),
-            missing_macros: HashSet::new(),
-            known_replacement: HashSet::new(),
+            missing_macros: BTreeMap::new(),
+            expansions: Vec::new(),
+            expansion_indices: HashMap::new(),
             disable_macro_expand: false,
             parsing_tokens: false,
             text,
             path,
             last_parse_error: None,
             include: IncludeHandler::new(options),
+            module_name: None,
+            current_function: None,
+            current_function_arity: None,
+            conditionals: Vec::new(),
+            otp_release: Self::DEFAULT_OTP_RELEASE,
+            features: BTreeMap::new(),
+            verbatim_literals,
+            allow_partial: false,
+            recover_errors: false,
+            recovered_errors: Vec::new(),
+            is_tokenizer_stopped: false,
         }
     }
 
+    /// The default value of `?OTP_RELEASE`.
+    pub const DEFAULT_OTP_RELEASE: u32 = 28;
+
+    /// Sets the value of `?OTP_RELEASE`.
+    ///
+    /// This also affects the values of `?FEATURE_AVAILABLE(..)` and `?FEATURE_ENABLED(..)`.
+    pub fn set_otp_release(&mut self, release: u32) {
+        self.otp_release = release;
+    }
+
+    /// If enabled, forms that could not be parsed are kept in the parsed module as they are
+    /// instead of making the whole parse fail (see [crate::items::forms::BrokenForm]).
+    ///
+    /// This implies [TokenStream::set_recover_errors].
+    pub fn set_allow_partial(&mut self, enable: bool) {
+        self.allow_partial = enable;
+    }
+
+    pub(crate) fn allow_partial(&self) -> bool {
+        self.allow_partial
+    }
+
+    /// If enabled, the forms that could not be parsed are skipped to continue parsing the following forms,
+    /// so that all of the errors can be obtained by [TokenStream::recovered_errors].
+    ///
+    /// Unless [TokenStream::set_allow_partial] is enabled, the whole parse still fails at the end.
+    pub fn set_recover_errors(&mut self, enable: bool) {
+        self.recover_errors = enable;
+    }
+
+    pub(crate) fn recover_errors(&self) -> bool {
+        self.recover_errors || self.allow_partial
+    }
+
+    /// Calls `f` with the error recovery (and [TokenStream::set_allow_partial]) disabled.
+    pub(crate) fn with_error_recovery_disabled<F, T>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let flags = (self.allow_partial, self.recover_errors);
+        self.allow_partial = false;
+        self.recover_errors = false;
+        let result = f(self);
+        (self.allow_partial, self.recover_errors) = flags;
+        result
+    }
+
+    /// Returns the include files and macros that could not be resolved so far.
+    pub fn missing(&self) -> Missing {
+        Missing {
+            includes: self.include.unresolved().clone(),
+            macros: self.missing_macros.clone(),
+        }
+    }
+
+    /// Returns the errors of the forms skipped by [TokenStream::set_recover_errors] or
+    /// [TokenStream::set_allow_partial].
+    pub fn recovered_errors(&self) -> &[Error] {
+        &self.recovered_errors
+    }
+
+    /// Skips the tokens until the end of the current form, and returns the skipped range.
+    ///
+    /// The form is assumed to end with a `.` that is followed by a newline and a line starting at column 0.
+    /// If a tokenize error occurs, the rest of the text is skipped.
+    pub(crate) fn skip_broken_form(&mut self, error: Error) -> (Position, Position) {
+        let start = self
+            .tokens
+            .get(self.current_token_index)
+            .map_or_else(|| error.position(), |x| x.token.start_position());
+        self.recovered_errors.push(error);
+
+        let result = self.with_macro_expand_disabled(|ts| loop {
+            let Some(token) = ts.next().transpose()? else {
+                return Ok(ts.prev_token_end_position().max(start));
+            };
+            if let LexicalToken::Symbol(x) = &token {
+                let end = x.end_position();
+                if x.value() == Symbol::Dot
+                    && ts.text[x.start_position().offset()..].starts_with('.')
+                    && is_form_end(&ts.text, end.offset())
+                {
+                    return Ok(end);
+                }
+            }
+        });
+        let end = result.unwrap_or_else(|_| {
+            self.is_tokenizer_stopped = true;
+            text_end_position(&self.text)
+        });
+
+        // Macro calls in the skipped range are kept as they are.
+        self.macros.retain(|k, _| *k < start || end <= *k);
+        (start, end)
+    }
+
+    /// Sets the name of the function that is being parsed (i.e., the value of `?FUNCTION_NAME`).
+    pub(crate) fn set_current_function(&mut self, name: &str) {
+        self.current_function = Some(name.to_owned());
+    }
+
+    /// Sets the arity of the function that is being parsed (i.e., the value of `?FUNCTION_ARITY`).
+    pub(crate) fn set_current_function_arity(&mut self, arity: usize) {
+        self.current_function_arity = Some(arity);
+    }
+
+    /// Forgets the function set by [TokenStream::set_current_function] and
+    /// [TokenStream::set_current_function_arity] (called at every form boundary).
+    pub(crate) fn clear_current_function(&mut self) {
+        self.current_function = None;
+        self.current_function_arity = None;
+    }
+
     pub(crate) fn set_known_macro_defines(&mut self, macro_defines: MacroDefines) {
         assert!(self.macro_defines.is_empty());
         self.macro_defines = macro_defines;
@@ -79,6 +245,7 @@ impl TokenStream {
     }
 
     pub fn parse_tokens<T: Parse>(&mut self, tokens: Vec<LexicalToken>) -> Result<T> {
+        let tokens = tokens.into_iter().map(StreamToken::new).collect();
         let old_tokens = std::mem::replace(&mut self.tokens, tokens);
         let old_index = self.current_token_index;
         let old_last_parse_error = self.last_parse_error.take();
@@ -98,26 +265,39 @@ impl TokenStream {
     pub fn parse<T: Parse>(&mut self) -> Result<T> {
         let index = self.current_token_index;
         let result = T::parse(self);
-        if let Err(e) = &result {
-            self.current_token_index = index;
-            if self
-                .last_parse_error
-                .as_ref()
-                .map_or(true, |e0| e0.position() < e.position())
-            {
-                self.last_parse_error = Some(e.clone());
+        match result {
+            Err(e) => {
+                self.current_token_index = index;
+                Err(self.record_parse_error(e))
             }
-        } else {
-            let succeeded_position = self.prev_token_end_position();
-            if self
-                .last_parse_error
-                .as_ref()
-                .map_or(false, |e| e.position() < succeeded_position)
-            {
-                self.last_parse_error = None;
+            Ok(x) => {
+                let succeeded_position = self.prev_token_end_position();
+                if self.last_parse_error.as_ref().is_some_and(|e| match e {
+                    Error::UnexpectedToken { token_index, .. } => {
+                        *token_index < self.current_token_index
+                    }
+                    _ => e.position() < succeeded_position,
+                }) {
+                    self.last_parse_error = None;
+                }
+                Ok(x)
             }
         }
-        result
+    }
+
+    // Records `e` if it is the furthest error, and returns it with the expected token kinds of
+    // the other alternatives that failed at the same position.
+    fn record_parse_error(&mut self, e: Error) -> Error {
+        let e = match self.last_parse_error.take() {
+            Some(e0) if e.progress() < e0.progress() => {
+                self.last_parse_error = Some(e0);
+                return e;
+            }
+            Some(e0) if e.progress() == e0.progress() => e0.merge_expected(&e),
+            _ => e,
+        };
+        self.last_parse_error = Some(e.clone());
+        e
     }
 
     pub fn take_last_error(&mut self) -> Option<Error> {
@@ -136,10 +316,42 @@ impl TokenStream {
         result
     }
 
+    /// Returns the index of the token that has been read (or peeked) last.
+    ///
+    /// The index is recorded when the token is read, so it identifies the token
+    /// even if the same token appears multiple times (e.g., in macro expansions).
+    pub(crate) fn last_token_index(&self) -> usize {
+        self.last_token_index
+    }
+
+    /// Returns the macro expansions that produced the token at `index` (the innermost first).
+    pub(crate) fn expansion_notes(&self, index: usize) -> Vec<ExpansionNote> {
+        let mut expansion = self.tokens.get(index).and_then(|x| x.expansion);
+
+        let mut notes = Vec::new();
+        while let Some(i) = expansion {
+            let x = &self.expansions[i];
+            let defined_at = self
+                .macro_defines
+                .get(&x.key)
+                .map(|d| (d.path.clone(), d.line));
+            notes.push(ExpansionNote::new(x.key.name().to_owned(), defined_at));
+            expansion = x.parent;
+        }
+        notes
+    }
+
     pub fn peek<T: Parse>(&mut self) -> Option<T> {
         let index = self.current_token_index;
+        let last_parse_error = self.last_parse_error.take();
         let result = self.parse::<T>().ok();
         self.current_token_index = index;
+
+        // As the peeked tokens are not consumed, the errors before them are still valid.
+        let peek_error = std::mem::replace(&mut self.last_parse_error, last_parse_error);
+        if let Some(e) = peek_error.filter(|_| result.is_none()) {
+            self.record_parse_error(e);
+        }
         result
     }
 
@@ -180,7 +392,7 @@ impl TokenStream {
 
     pub fn prev_token_end_position(&self) -> Position {
         if let Some(i) = self.current_token_index.checked_sub(1) {
-            self.tokens[i].end_position()
+            self.tokens[i].token.end_position()
         } else {
             self.tokenizer.next_position().into()
         }
@@ -191,26 +403,26 @@ impl TokenStream {
         if index == self.tokens.len() && self.is_eof()? {
             Ok(self.tokenizer.next_position().into())
         } else {
-            Ok(self.tokens[index].start_position())
+            Ok(self.tokens[index].token.start_position())
         }
     }
 
     fn read_token(&mut self) -> Result<Option<LexicalToken>> {
-        if let Some(token) = self.tokens.get(self.current_token_index).cloned() {
+        if let Some(x) = self.tokens.get(self.current_token_index) {
             self.current_token_index += 1;
 
             if !self.disable_macro_expand {
-                match &token {
-                    LexicalToken::Symbol(x) if x.value() == Symbol::Question => {
+                match &x.token {
+                    LexicalToken::Symbol(y) if y.value() == Symbol::Question => {
                         return self.expand_macro_and_read_token();
                     }
                     _ => {}
                 }
             }
 
-            return Ok(Some(token));
+            return Ok(Some(x.token.clone()));
         }
-        if self.parsing_tokens {
+        if self.parsing_tokens || self.is_tokenizer_stopped {
             return Ok(None);
         }
 
@@ -227,8 +439,8 @@ impl TokenStream {
                     continue;
                 }
                 erl_tokenize::Token::Comment(x) => {
-                    let is_trailing = self.tokens.last().map_or(false, |y| {
-                        y.start_position().line() == x.start_position().line()
+                    let is_trailing = self.tokens.last().is_some_and(|y| {
+                        y.token.start_position().line() == x.start_position().line()
                     });
                     let kind = if is_trailing {
                         CommentKind::Trailing
@@ -247,16 +459,24 @@ impl TokenStream {
                 erl_tokenize::Token::Atom(x) => {
                     AtomToken::new(x.value(), start_position, end_position).into()
                 }
-                erl_tokenize::Token::Char(_) => CharToken::new(start_position, end_position).into(),
-                erl_tokenize::Token::Float(_) => {
-                    FloatToken::new(start_position, end_position).into()
+                erl_tokenize::Token::Char(x) => {
+                    CharToken::new(x.text(), start_position, end_position).into()
+                }
+                erl_tokenize::Token::Float(x) => {
+                    FloatToken::new(x.text(), start_position, end_position).into()
                 }
-                erl_tokenize::Token::Integer(_) => {
-                    IntegerToken::new(start_position, end_position).into()
+                erl_tokenize::Token::Integer(x) => {
+                    IntegerToken::new(x.value().to_string(), start_position, end_position).into()
                 }
                 erl_tokenize::Token::Keyword(x) => {
                     KeywordToken::new(x.value(), start_position, end_position).into()
                 }
+                erl_tokenize::Token::String(_)
+                    if self.verbatim_literals.contains(&start_position.offset()) =>
+                {
+                    let literal = &self.text[start_position.offset()..end_position.offset()];
+                    StringToken::new_verbatim(literal, start_position, end_position).into()
+                }
                 erl_tokenize::Token::String(x) => {
                     StringToken::new(x.value(), start_position, end_position).into()
                 }
@@ -264,14 +484,14 @@ impl TokenStream {
                     VariableToken::new(x.value(), start_position, end_position).into()
                 }
             };
-            self.tokens.push(token.clone());
+            self.tokens.push(StreamToken::new(token.clone()));
             self.current_token_index += 1;
 
             match &token {
-                LexicalToken::Symbol(x) if x.value() == Symbol::Question => {
-                    if !self.disable_macro_expand {
-                        return self.expand_macro_and_read_token();
-                    }
+                LexicalToken::Symbol(x)
+                    if x.value() == Symbol::Question && !self.disable_macro_expand =>
+                {
+                    return self.expand_macro_and_read_token();
                 }
                 LexicalToken::Symbol(x) if x.value() == Symbol::Hyphen => {
                     let index = self.current_token_index;
@@ -305,81 +525,174 @@ impl TokenStream {
     }
 
     fn expand_macro_and_read_token(&mut self) -> Result<Option<LexicalToken>> {
+        let start_index = self.current_token_index - 1;
         let macro_name: MacroName = self.parse()?;
-        self.expand_macro(macro_name)?;
+        self.expand_macro(start_index, macro_name)?;
         self.read_token()
     }
 
-    fn expand_macro(&mut self, macro_name: MacroName) -> Result<()> {
+    fn expand_macro(&mut self, start_index: usize, macro_name: MacroName) -> Result<()> {
         match self.is_macro_defined(macro_name.value()) {
-            (false, false) => self.expand_unknown_macro(macro_name),
-            (true, false) => {
-                let key = MacroDefineKey::new(macro_name.value().to_owned(), None);
-                self.expand_macro_without_args(
-                    macro_name,
-                    self.macro_defines[&key].replacement.clone(),
-                )
-            }
+            (false, false) => self.expand_unknown_macro(start_index, macro_name),
+            (true, false) => self.expand_macro_without_args(start_index, macro_name),
             (true, true) if self.peek::<OpenParenSymbol>().is_none() => {
-                let key = MacroDefineKey::new(macro_name.value().to_owned(), None);
-                self.expand_macro_without_args(
-                    macro_name,
-                    self.macro_defines[&key].replacement.clone(),
-                )
+                self.expand_macro_without_args(start_index, macro_name)
             }
-            (_, _) => self.expand_macro_with_args(macro_name),
+            (_, _) => self.expand_macro_with_args(start_index, macro_name),
         }
     }
 
-    fn expand_macro_with_args(&mut self, macro_name: MacroName) -> Result<()> {
-        let start_index = self.current_token_index - 2;
-        let start_position = self.tokens[start_index].start_position();
+    // Parses a macro call with arguments without affecting the errors of the ongoing parse,
+    // as the tokens of the call are replaced with the expanded tokens.
+    fn parse_macro_call(
+        &mut self,
+        question: QuestionSymbol,
+        macro_name: MacroName,
+    ) -> Result<Macro> {
+        let last_parse_error = self.last_parse_error.take();
+        let result =
+            self.with_macro_expand_disabled(|ts| ts.resume_parse((question, macro_name, true)));
+        self.last_parse_error = last_parse_error;
+        result
+    }
+
+    fn expand_macro_with_args(&mut self, start_index: usize, macro_name: MacroName) -> Result<()> {
+        let start_position = self.tokens[start_index].token.start_position();
         let question = QuestionSymbol::new(start_position);
 
-        let r#macro: Macro = self.with_macro_expand_disabled(|ts| {
-            ts.resume_parse((question, macro_name.clone(), true))
-        })?;
+        let r#macro: Macro = self.parse_macro_call(question, macro_name.clone())?;
         let arity = r#macro.arity();
         assert!(arity.is_some());
 
         let key = MacroDefineKey::new(macro_name.value().to_owned(), arity);
-        if let Some(define) = self.macro_defines.get(&key).cloned() {
-            let variables = define.variables.as_ref().map(|x| x.to_owned());
-
-            let replacement = r#macro.expand(variables, define.replacement);
-            self.replace_tokens(start_index, &key, replacement);
+        if let Some(define) = self.macro_defines.get(&key) {
+            let replacement = r#macro.expand(define.variables.as_deref(), &define.replacement);
+            self.replace_tokens(start_index, key, replacement);
             self.macros.entry(start_position).or_insert(r#macro);
+            Ok(())
         } else {
-            self.expand_unknown_macro(macro_name)?;
+            self.current_token_index = start_index + 2;
+            self.expand_unknown_macro(start_index, macro_name)
         }
+    }
+
+    fn expand_macro_without_args(
+        &mut self,
+        start_index: usize,
+        macro_name: MacroName,
+    ) -> Result<()> {
+        let key = MacroDefineKey::new(macro_name.value().to_owned(), None);
+        let start_position = self.tokens[start_index].token.start_position();
+        let question = QuestionSymbol::new(start_position);
+        let r#macro: Macro = self.resume_parse((question, macro_name, false))?;
+
+        let replacement = r#macro.expand(None, &self.macro_defines[&key].replacement);
+        self.replace_tokens(start_index, key, replacement);
+        self.macros.entry(start_position).or_insert(r#macro);
 
         Ok(())
     }
 
-    fn expand_macro_without_args(
+    fn expand_predefined_macro(
         &mut self,
+        start_index: usize,
         macro_name: MacroName,
         replacement: Vec<LexicalToken>,
     ) -> Result<()> {
         let key = MacroDefineKey::new(macro_name.value().to_owned(), None);
-        let start_index = self.current_token_index - 2;
-        let start_position = self.tokens[start_index].start_position();
+        let start_position = self.tokens[start_index].token.start_position();
         let question = QuestionSymbol::new(start_position);
         let r#macro: Macro = self.resume_parse((question, macro_name, false))?;
 
-        let replacement = r#macro.expand(None, replacement);
-        self.replace_tokens(start_index, &key, replacement);
+        let replacement = r#macro.expand(None, &replacement);
+        self.replace_tokens(start_index, key, replacement);
         self.macros.entry(start_position).or_insert(r#macro);
 
         Ok(())
     }
 
-    fn expand_unknown_macro(&mut self, macro_name: MacroName) -> Result<()> {
-        let start_index = self.current_token_index - 2;
-        let start_position = self.tokens[start_index].start_position();
+    fn expand_feature_macro(&mut self, start_index: usize, macro_name: MacroName) -> Result<()> {
+        let start_position = self.tokens[start_index].token.start_position();
+        let question = QuestionSymbol::new(start_position);
+
+        let r#macro: Macro = self.parse_macro_call(question, macro_name.clone())?;
+        let feature = match r#macro.arg_tokens().as_deref() {
+            Some([[LexicalToken::Atom(x)]]) => Some(x.value()),
+            _ => None,
+        };
+        let value = match (macro_name.value(), feature) {
+            ("FEATURE_AVAILABLE", Some(feature)) => self.is_feature_available(feature),
+            ("FEATURE_ENABLED", Some(feature)) => self.is_feature_enabled(feature),
+            _ => false,
+        };
+        let replacement = vec![LexicalToken::from(AtomToken::new(
+            if value { "true" } else { "false" },
+            start_position,
+            start_position,
+        ))];
 
-        if let Some(replacement) = get_predefined_macro(macro_name.value(), start_position) {
-            self.expand_macro_without_args(macro_name, replacement)
+        let key = MacroDefineKey::new(macro_name.value().to_owned(), r#macro.arity());
+        let replacement = r#macro.expand(None, &replacement);
+        self.replace_tokens(start_index, key, replacement);
+        self.macros.entry(start_position).or_insert(r#macro);
+        Ok(())
+    }
+
+    fn is_feature_available(&self, feature: &str) -> bool {
+        match feature {
+            "maybe_expr" => self.otp_release >= 25,
+            _ => false,
+        }
+    }
+
+    fn is_feature_enabled(&self, feature: &str) -> bool {
+        if let Some(enabled) = self.features.get(feature) {
+            return *enabled && self.is_feature_available(feature);
+        }
+        match feature {
+            "maybe_expr" => self.otp_release >= 27,
+            _ => false,
+        }
+    }
+
+    fn get_predefined_macro(&self, name: &str, start_index: usize) -> Option<Vec<LexicalToken>> {
+        let position = self.tokens[start_index].token.start_position();
+        let module_name = self.module_name.as_deref().unwrap_or("EFMT_DUMMY");
+        let token: LexicalToken = match name {
+            "MODULE" => AtomToken::new(module_name, position, position).into(),
+            "MODULE_STRING" => StringToken::new(module_name, position, position).into(),
+            "FUNCTION_NAME" => {
+                let function_name = self.current_function.as_deref().unwrap_or("EFMT_DUMMY");
+                AtomToken::new(function_name, position, position).into()
+            }
+            "FUNCTION_ARITY" => {
+                let arity = self.current_function_arity.unwrap_or(0);
+                IntegerToken::new(arity.to_string(), position, position).into()
+            }
+            "FILE" => {
+                let path = self.path.as_ref().and_then(|p| p.to_str());
+                StringToken::new(path.unwrap_or("EFMT_DUMMY"), position, position).into()
+            }
+            "MACHINE" => AtomToken::new("BEAM", position, position).into(),
+            "LINE" => IntegerToken::new(position.line().to_string(), position, position).into(),
+            "OTP_RELEASE" => {
+                IntegerToken::new(self.otp_release.to_string(), position, position).into()
+            }
+            _ => return None,
+        };
+        Some(vec![token])
+    }
+
+    fn expand_unknown_macro(&mut self, start_index: usize, macro_name: MacroName) -> Result<()> {
+        let start_position = self.tokens[start_index].token.start_position();
+
+        if matches!(macro_name.value(), "FEATURE_AVAILABLE" | "FEATURE_ENABLED")
+            && self.peek::<OpenParenSymbol>().is_some()
+        {
+            self.expand_feature_macro(start_index, macro_name)
+        } else if let Some(replacement) = self.get_predefined_macro(macro_name.value(), start_index)
+        {
+            self.expand_predefined_macro(start_index, macro_name, replacement)
         } else if self.disable_macro_expand {
             log::debug!(
                 "Found an undefined macro {:?} in disabling macro expansions.",
@@ -387,69 +700,169 @@ impl TokenStream {
             );
             Ok(())
         } else {
-            if !self.missing_macros.contains(macro_name.value()) {
+            if !self.missing_macros.contains_key(macro_name.value()) {
                 log::warn!(
                     "The macro {:?} is not defined. 'EFMT_DUMMY' atom is used instead.",
                     macro_name.value()
                 );
-                self.missing_macros.insert(macro_name.value().to_owned());
+                self.missing_macros
+                    .insert(macro_name.value().to_owned(), start_position);
             }
-            self.expand_macro_without_args(
+            self.expand_predefined_macro(
+                start_index,
                 macro_name,
                 vec![LexicalToken::from(dummy_atom(start_position))],
             )
         }
     }
 
+    /// Replaces the tokens of a macro call (i.e., `self.tokens[start_index..self.current_token_index]`)
+    /// with the expanded tokens.
+    ///
+    /// The expanded tokens that came from the macro definition are associated with a new [Expansion]
+    /// so that circular (or too deep) macro expansions can be detected without copying any tokens.
     fn replace_tokens(
         &mut self,
         start_index: usize,
-        key: &MacroDefineKey,
-        mut replacement: Vec<LexicalToken>,
+        key: MacroDefineKey,
+        mut replacement: Vec<ExpandedToken>,
     ) {
-        if !replacement.is_empty()
-            && !self
-                .known_replacement
-                .insert((start_index, replacement.clone()))
-        {
-            log::warn!(
-                "A circular macro {:?} was detected. It was replaced with a dummy atom 'EFMT_DUMMY'.",
-                  key.to_string(),
-            );
-            let start_position = self.tokens[start_index].start_position();
-            replacement = vec![LexicalToken::from(dummy_atom(start_position))];
-            self.macro_defines
-                .get_mut(key)
-                .expect("unreachable")
-                .replacement = replacement.clone();
+        let parent = self.tokens[start_index].expansion;
+        match self.check_expansion(parent, &key) {
+            Ok(()) => {}
+            Err(reason) => {
+                log::warn!(
+                    "A {} macro {:?} was detected. It was replaced with a dummy atom 'EFMT_DUMMY'.",
+                    reason,
+                    key.to_string(),
+                );
+                let start_position = self.tokens[start_index].token.start_position();
+                replacement = vec![ExpandedToken::Body(dummy_atom(start_position).into())];
+            }
         }
 
-        let unread_tokens = self.tokens.split_off(self.current_token_index);
-        self.tokens.truncate(start_index);
-        self.tokens.extend(replacement);
-        self.tokens.extend(unread_tokens);
+        let expansion = self.intern_expansion(Expansion { key, parent });
+        let replacement = replacement.into_iter().map(|x| match x {
+            ExpandedToken::Body(token) => StreamToken {
+                token,
+                expansion: Some(expansion),
+            },
+            ExpandedToken::Arg(token) => StreamToken {
+                token,
+                expansion: parent,
+            },
+        });
+        self.tokens
+            .replace(start_index..self.current_token_index, replacement);
         self.current_token_index = start_index;
     }
 
+    // Returns the index of `expansion` in `self.expansions`.
+    //
+    // As expansions with the same macro and parent are indistinguishable, they share the same index.
+    // So the number of expansions is bounded by the distinct expansion chains rather than the macro calls.
+    fn intern_expansion(&mut self, expansion: Expansion) -> usize {
+        if let Some(&i) = self.expansion_indices.get(&expansion) {
+            return i;
+        }
+        let i = self.expansions.len();
+        self.expansions.push(expansion.clone());
+        self.expansion_indices.insert(expansion, i);
+        i
+    }
+
+    fn check_expansion(
+        &self,
+        mut expansion: Option<usize>,
+        key: &MacroDefineKey,
+    ) -> std::result::Result<(), &'static str> {
+        let mut depth = 0;
+        while let Some(i) = expansion {
+            let x = &self.expansions[i];
+            if x.key == *key {
+                return Err("circular");
+            }
+            depth += 1;
+            if depth >= MAX_MACRO_EXPANSION_DEPTH {
+                return Err("too deeply nested");
+            }
+            expansion = x.parent;
+        }
+        Ok(())
+    }
+
     fn try_handle_directives(&mut self) -> Result<()> {
         self.current_token_index -= 1;
         let result: Result<Either<DefineDirective, IncludeDirective>> = self.parse();
         match result {
             Ok(Either::A(x)) => {
                 let name = x.macro_name().to_owned();
-                let define: MacroDefine = x.into();
+                let define = MacroDefine::new(x, self.path.as_deref().cloned());
                 let key = MacroDefineKey::new(name, define.arity());
+
+                // A definition in an inactive branch (e.g., the `-else` branch of `-if(?OTP_RELEASE >= 26)`)
+                // doesn't override the one in the active branch, but is used if there are no other definitions.
+                if self.is_in_inactive_branch() && self.macro_defines.contains_key(&key) {
+                    return Ok(());
+                }
                 self.new_macro_defines.insert(key.clone());
                 self.macro_defines.insert(key, define);
             }
             Ok(Either::B(x)) => {
                 self.handle_include(x);
             }
-            Err(_) => {}
+            Err(_) => {
+                let result: Result<Either<ConditionalDirective, Either<ModuleAttr, FeatureAttr>>> =
+                    self.parse();
+                match result {
+                    Ok(Either::A(x)) => {
+                        self.handle_conditional(x);
+                    }
+                    Ok(Either::B(Either::A(x))) => {
+                        self.module_name = Some(x.module_name().to_owned());
+                    }
+                    Ok(Either::B(Either::B(x))) => {
+                        self.features
+                            .insert(x.feature_name().to_owned(), x.is_enable());
+                    }
+                    Err(_) => {}
+                }
+            }
         }
         Ok(())
     }
 
+    fn handle_conditional(&mut self, directive: ConditionalDirective) {
+        let condition = match directive.kind() {
+            ConditionalKind::If | ConditionalKind::Elif => {
+                crate::parse::condition::evaluate(directive.args())
+            }
+            ConditionalKind::Else => Some(true),
+            ConditionalKind::Ifdef | ConditionalKind::Ifndef => None,
+            ConditionalKind::Endif => {
+                self.conditionals.pop();
+                return;
+            }
+        };
+        match directive.kind() {
+            ConditionalKind::If | ConditionalKind::Ifdef | ConditionalKind::Ifndef => {
+                self.conditionals.push(Conditional {
+                    active: condition,
+                    taken: condition,
+                });
+            }
+            _ => {
+                if let Some(x) = self.conditionals.last_mut() {
+                    x.enter_branch(condition);
+                }
+            }
+        }
+    }
+
+    fn is_in_inactive_branch(&self) -> bool {
+        self.conditionals.iter().any(|x| x.active == Some(false))
+    }
+
     fn handle_include(&mut self, include: IncludeDirective) {
         let new_macro_defines = self.include.include_macro_defines(
             self.filepath().as_deref(),
@@ -462,34 +875,99 @@ impl TokenStream {
     }
 }
 
+/// A token in [TokenStream]'s buffer.
+#[derive(Debug, Clone)]
+struct StreamToken {
+    token: LexicalToken,
+
+    /// The macro expansion that generated this token (`None` if the token came from the source text).
+    expansion: Option<usize>,
+}
+
+impl StreamToken {
+    fn new(token: LexicalToken) -> Self {
+        Self {
+            token,
+            expansion: None,
+        }
+    }
+}
+
+/// A macro expansion that has been performed in [TokenStream].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Expansion {
+    key: MacroDefineKey,
+
+    /// The expansion that contains the macro call of this expansion.
+    parent: Option<usize>,
+}
+
+/// A conditional compilation block (`-if`, `-ifdef` or `-ifndef` ... `-endif`) that is being read.
+///
+/// `None` means that the value is unknown, e.g., `-ifdef` whose macro may be defined in an unresolved include file.
+#[derive(Debug, Clone, Copy)]
+struct Conditional {
+    /// Whether the current branch is taken.
+    active: Option<bool>,
+
+    /// Whether the current or a preceding branch is taken.
+    taken: Option<bool>,
+}
+
+impl Conditional {
+    // Moves to the next branch (`-elif` or `-else`) whose condition is `condition`.
+    fn enter_branch(&mut self, condition: Option<bool>) {
+        self.active = match (self.taken, condition) {
+            (Some(true), _) | (_, Some(false)) => Some(false),
+            (Some(false), condition) => condition,
+            (None, _) => None,
+        };
+        self.taken = match (self.taken, self.active) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        };
+    }
+}
+
 impl Iterator for TokenStream {
     type Item = Result<LexicalToken>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.read_token().transpose()
+        let token = self.read_token().transpose();
+        if let Some(Ok(_)) = &token {
+            // `read_token()` always leaves the index just after the returned token
+            // (the tokens of a macro call are replaced with the expanded ones before reading).
+            self.last_token_index = self.current_token_index - 1;
+        }
+        token
     }
 }
 
-fn get_predefined_macro(name: &str, position: Position) -> Option<Vec<LexicalToken>> {
-    let token: LexicalToken = match name {
-        "MODULE" | "FUNCTION_NAME" => dummy_atom(position).into(),
-        "LINE" | "FUNCTION_ARITY" | "OTP_RELEASE" => dummy_integer(position).into(),
-        "MODULE_STRING" | "FILE" | "MACHINE" => dummy_string(position).into(),
-        _ => return None,
+// Returns `true` if the text after `offset` is a newline followed by a line starting at column 0 (or EOF).
+fn is_form_end(text: &str, offset: usize) -> bool {
+    let rest = &text[offset..];
+    let Some(newline) = rest.find('\n') else {
+        return rest.trim().is_empty() || rest.trim_start().starts_with('%');
     };
-    Some(vec![token])
-}
-
-fn dummy_atom(position: Position) -> AtomToken {
-    AtomToken::new("EFMT_DUMMY", position, position)
+    let line = rest[..newline].trim();
+    if !(line.is_empty() || line.starts_with('%')) {
+        return false;
+    }
+    rest[newline + 1..]
+        .lines()
+        .find(|x| !x.trim().is_empty())
+        .is_none_or(|x| !x.starts_with(char::is_whitespace))
 }
 
-fn dummy_integer(position: Position) -> IntegerToken {
-    IntegerToken::new(position, position)
+fn text_end_position(text: &str) -> Position {
+    let line = text.matches('\n').count() + 1;
+    let last_line = &text[text.rfind('\n').map_or(0, |i| i + 1)..];
+    Position::new(text.len(), line, last_line.chars().count() + 1)
 }
 
-fn dummy_string(position: Position) -> StringToken {
-    StringToken::new("EFMT_DUMMY", position, position)
+fn dummy_atom(position: Position) -> AtomToken {
+    AtomToken::new("EFMT_DUMMY", position, position)
 }
 
 #[derive(
@@ -524,23 +1002,180 @@ impl std::fmt::Display for MacroDefineKey {
 pub(crate) struct MacroDefine {
     variables: Option<Vec<String>>,
     replacement: Vec<LexicalToken>,
+
+    /// The path of the file containing the `-define` directive.
+    path: Option<PathBuf>,
+
+    /// The line number of the `-define` directive.
+    line: usize,
 }
 
-impl From<DefineDirective> for MacroDefine {
-    fn from(x: DefineDirective) -> Self {
+impl MacroDefine {
+    fn new(x: DefineDirective, path: Option<PathBuf>) -> Self {
         Self {
             variables: x
                 .variables()
                 .map(|v| v.iter().map(|v| v.value().to_owned()).collect()),
             replacement: x.replacement().to_owned(),
+            path,
+            line: x.start_position().line(),
         }
     }
-}
 
-impl MacroDefine {
     pub(crate) fn arity(&self) -> Option<usize> {
         self.variables.as_ref().map(|x| x.len())
     }
 }
 
 pub(crate) type MacroDefines = BTreeMap<MacroDefineKey, MacroDefine>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::Module;
+
+    fn expanded_values(text: &str, otp_release: u32) -> Vec<String> {
+        let mut ts = TokenStream::new(
+            Tokenizer::new(text.to_owned()),
+            IncludeOptions::new().disable_include(),
+        );
+        ts.set_otp_release(otp_release);
+        let _: Module = ts.parse().unwrap();
+
+        ts.tokens
+            .iter()
+            .filter(|x| x.expansion.is_some())
+            .filter_map(|x| match &x.token {
+                LexicalToken::Atom(x) => Some(x.value().to_owned()),
+                LexicalToken::String(x) => Some(format!("{:?}", x.value())),
+                LexicalToken::Integer(x) => Some(x.value().to_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn predefined_macros_work() {
+        let text = indoc::indoc! {"
+        -module(foo).
+        bar(A, B) ->
+            {?MODULE, ?MODULE_STRING, ?FUNCTION_NAME, ?FUNCTION_ARITY, ?MACHINE, ?LINE}.
+        "};
+        assert_eq!(
+            expanded_values(text, 26),
+            ["foo", "\"foo\"", "bar", "2", "BEAM", "3"]
+        );
+    }
+
+    #[test]
+    fn function_macros_work() {
+        let text = indoc::indoc! {"
+        -module(foo).
+        -spec bar(atom()) -> ?FUNCTION_ARITY.
+        bar(A) when ?FUNCTION_ARITY =:= 1 ->
+            fun (X, Y) -> {?FUNCTION_NAME, ?FUNCTION_ARITY} end.
+        -define(BAZ, {?FUNCTION_NAME, ?FUNCTION_ARITY}).
+        baz() ->
+            ?BAZ.
+        -export([?FUNCTION_NAME/0]).
+        "};
+        assert_eq!(
+            expanded_values(text, 26),
+            ["1", "1", "bar", "1", "baz", "0", "EFMT_DUMMY"]
+        );
+    }
+
+    #[test]
+    fn conditional_directives_work() {
+        let text = indoc::indoc! {"
+        -if(?OTP_RELEASE >= 26).
+        -define(A, new).
+        -elif(?OTP_RELEASE >= 25).
+        -define(A, middle).
+        -else.
+        -define(A, old).
+        -define(B, only_old).
+        -endif.
+        -ifdef(FOO).
+        -define(C, foo).
+        -else.
+        -define(C, no_foo).
+        -endif.
+        bar() ->
+            {?A, ?B, ?C}.
+        "};
+        assert_eq!(
+            expanded_values(text, 27),
+            ["27", "27", "new", "only_old", "no_foo"]
+        );
+        assert_eq!(
+            expanded_values(text, 25),
+            ["25", "25", "middle", "only_old", "no_foo"]
+        );
+        assert_eq!(
+            expanded_values(text, 24),
+            ["24", "24", "old", "only_old", "no_foo"]
+        );
+    }
+
+    #[test]
+    fn feature_macros_work() {
+        let text = indoc::indoc! {"
+        -module(foo).
+        -feature(maybe_expr, enable).
+        bar() ->
+            {?FEATURE_AVAILABLE(maybe_expr), ?FEATURE_ENABLED(maybe_expr), ?FEATURE_ENABLED(baz)}.
+        "};
+        assert_eq!(expanded_values(text, 26), ["true", "true", "false"]);
+        assert_eq!(expanded_values(text, 24), ["false", "false", "false"]);
+
+        let text = indoc::indoc! {"
+        -module(foo).
+        bar() ->
+            {?OTP_RELEASE, ?FEATURE_ENABLED(maybe_expr)}.
+        "};
+        assert_eq!(expanded_values(text, 26), ["26", "false"]);
+        assert_eq!(expanded_values(text, 27), ["27", "true"]);
+    }
+
+    #[test]
+    fn expansions_are_shared_between_macro_calls() {
+        let mut text = String::from("-define(ID(X), X).\n-define(PAIR(A, B), {?ID(A), ?ID(B)}).\n");
+        for i in 0..100 {
+            text.push_str(&format!("foo_{i}() -> ?PAIR(?ID(1), ?MODULE).\n"));
+        }
+        let mut ts = TokenStream::new(
+            Tokenizer::new(text),
+            IncludeOptions::new().disable_include(),
+        );
+        let _: Module = ts.parse().unwrap();
+
+        // The distinct expansion chains are `?PAIR`, `?ID` in `?PAIR` and `?MODULE` in `?PAIR`
+        // (the arguments are regarded as a part of the expansion containing the macro call).
+        assert_eq!(ts.expansions.len(), 3);
+    }
+
+    #[test]
+    fn stringify_macro_arg_works() {
+        let text = indoc::indoc! {"
+        -define(S(X), ??X).
+        -define(ID(X), X).
+        -define(PAIR(X, Y), {??X, ??Y}).
+        -define(INC(X), ?S(X + 1.5)).
+        -define(NO_ARG, ??X).
+        bar() ->
+            {?S(foo(1,  2)), ?ID(?S([a, 'B'])), ?PAIR(c, \"d\"), ?INC($a), ?NO_ARG}.
+        "};
+        assert_eq!(
+            expanded_values(text, 26),
+            [
+                "\"foo ( 1 , 2 )\"",
+                "\"[ a , 'B' ]\"",
+                "\"c\"",
+                "\"\\\"d\\\"\"",
+                "\"$a + 1.5\"",
+                "\"EFMT_DUMMY\""
+            ]
+        );
+    }
+}
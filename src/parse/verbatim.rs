@@ -0,0 +1,212 @@
+//! Triple-quoted strings and sigils introduced in OTP 27.
+//!
+//! As `erl_tokenize` doesn't know these literals, they are replaced with plain string literals of the same length
+//! (in both bytes and characters) before tokenization, and then restored from the original text when creating [StringToken]s.
+//!
+//! [StringToken]: crate::items::tokens::StringToken
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Returns the byte ranges of the triple-quoted strings (e.g., `"""\nfoo\n"""`) and sigils (e.g., `~b"foo"`) in `text`.
+pub(crate) fn find_verbatim_literals(text: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut literals = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                i = text[i..].find('\n').map_or(bytes.len(), |n| i + n);
+            }
+            b'$' => {
+                // A character literal such as `$"` or `$\'`.
+                i += 1;
+                if bytes.get(i) == Some(&b'\\') {
+                    i += 1;
+                }
+                i += text[i..].chars().next().map_or(1, char::len_utf8);
+            }
+            b'\'' => {
+                i = skip_quoted(bytes, i + 1, b'\'', true);
+            }
+            b'"' => {
+                if let Some(end) = triple_quoted_string_end(text, i) {
+                    literals.push(i..end);
+                    i = end;
+                } else {
+                    i = skip_quoted(bytes, i + 1, b'"', true);
+                }
+            }
+            b'~' => {
+                if let Some(end) = sigil_end(text, i) {
+                    literals.push(i..end);
+                    i = end;
+                } else {
+                    i += 1;
+                }
+            }
+            _ => {
+                i += 1;
+            }
+        }
+    }
+    literals
+}
+
+/// Replaces the triple-quoted strings and sigils in `text` with plain string literals so that `erl_tokenize` can
+/// tokenize the text.
+///
+/// The positions (i.e., the byte offsets, lines and columns) of the other tokens are unchanged
+/// as each character in a literal is replaced with a blank character of the same UTF-8 length.
+pub(crate) fn mask_verbatim_literals(text: &str) -> Cow<'_, str> {
+    let literals = find_verbatim_literals(text);
+    if literals.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut masked = String::with_capacity(text.len());
+    let mut offset = 0;
+    for literal in literals {
+        masked.push_str(&text[offset..literal.start]);
+        masked.push('"');
+        masked.extend(
+            text[literal.start + 1..literal.end - 1]
+                .chars()
+                .map(mask_char),
+        );
+        masked.push('"');
+        offset = literal.end;
+    }
+    masked.push_str(&text[offset..]);
+    Cow::Owned(masked)
+}
+
+fn mask_char(c: char) -> char {
+    match c.len_utf8() {
+        _ if c == '\n' => '\n',
+        1 => ' ',
+        2 => '\u{a0}',
+        3 => '\u{3000}',
+        _ => '\u{e0020}',
+    }
+}
+
+// Returns the offset just after the closing quote.
+fn skip_quoted(bytes: &[u8], mut i: usize, quote: u8, escape: bool) -> usize {
+    while i < bytes.len() {
+        if escape && bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i] == quote {
+            return i + 1;
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+// A triple-quoted string starts with three or more double quotes followed by a newline,
+// and ends with a line consisting of the same number of double quotes (and indentation).
+fn triple_quoted_string_end(text: &str, start: usize) -> Option<usize> {
+    let quotes = text[start..].len() - text[start..].trim_start_matches('"').len();
+    if quotes < 3 {
+        return None;
+    }
+    let delimiter = &text[start..start + quotes];
+
+    let first_line_end = start + text[start..].find('\n')?;
+    if !text[start + quotes..first_line_end].trim().is_empty() {
+        return None;
+    }
+    let mut line_start = first_line_end + 1;
+    loop {
+        let line_end = text[line_start..]
+            .find('\n')
+            .map_or(text.len(), |n| line_start + n);
+        let line = text[line_start..line_end].trim_start();
+        if line.starts_with(delimiter) && !line[quotes..].starts_with('"') {
+            return Some(line_end - line.len() + quotes);
+        }
+        if line_end == text.len() {
+            return None;
+        }
+        line_start = line_end + 1;
+    }
+}
+
+// A sigil consists of `~`, an optional type name (e.g., `b` or `S`), a string enclosed by a delimiter,
+// and optional modifiers.
+fn sigil_end(text: &str, start: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut i = start + 1;
+    while bytes.get(i).is_some_and(u8::is_ascii_alphabetic) {
+        i += 1;
+    }
+    // Uppercase sigils (e.g., `~S"\n"`) don't support escape sequences.
+    let escape = !bytes.get(i - 1).is_some_and(u8::is_ascii_uppercase);
+
+    let end = if let Some(end) = triple_quoted_string_end(text, i) {
+        end
+    } else {
+        let close = match *bytes.get(i)? {
+            b'(' => b')',
+            b'[' => b']',
+            b'{' => b'}',
+            b'<' => b'>',
+            c @ (b'/' | b'|' | b'\'' | b'"' | b'`' | b'#') => c,
+            _ => return None,
+        };
+        skip_quoted(bytes, i + 1, close, escape)
+    };
+
+    let modifiers = text[end..]
+        .bytes()
+        .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        .count();
+    Some(end + modifiers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_verbatim_literals_works() {
+        let text = indoc::indoc! {r#"
+        foo() ->
+            %% ~"comment"
+            {$~, $", '~"', "~s\"", ~"a", ~b"b\"", ~S[c\], ~s{d}x,
+             """
+               "triple"
+               """,
+             ~B""""
+               """quadruple"""
+               """"}."#};
+        let literals = find_verbatim_literals(text)
+            .into_iter()
+            .map(|x| &text[x])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            literals,
+            [
+                r#"~"a""#,
+                r#"~b"b\"""#,
+                r#"~S[c\]"#,
+                r#"~s{d}x"#,
+                "\"\"\"\n       \"triple\"\n       \"\"\"",
+                "~B\"\"\"\"\n       \"\"\"quadruple\"\"\"\n       \"\"\"\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn mask_verbatim_literals_works() {
+        let text = "{~\"é\", \"\"\"\n  ü€\n  \"\"\", ~B\"😀\", X}";
+        let masked = mask_verbatim_literals(text);
+        assert_eq!(
+            masked,
+            "{\" \u{a0}\", \"  \n  \u{a0}\u{3000}\n    \", \"  \u{e0020}\", X}"
+        );
+        assert_eq!(masked.len(), text.len());
+        assert_eq!(masked.chars().count(), text.chars().count());
+    }
+}
@@ -0,0 +1,265 @@
+//! Opt-in rewrites that change the token sequence of a module.
+//!
+//! Unlike formatting, a rewrite cannot be validated by comparing the tokens of the original and the rewritten text,
+//! so each rule checks the equivalence of the two texts in its own way.
+use crate::items::Module;
+use crate::span::Span;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{PositionRange as _, Token, Tokenizer};
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+mod attrs;
+mod exports;
+mod includes;
+mod parens;
+mod strings;
+
+/// Rewrite rules that can be enabled by [crate::Options::rewrite].
+///
+/// The rules are applied in the order of this enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RewriteRule {
+    /// Removes the parentheses enclosing the value of `-spec`, `-callback`, `-type` or `-opaque`
+    /// (e.g., `-spec(foo() -> ok).` is rewritten to `-spec foo() -> ok.`).
+    SpecParens,
+
+    /// Concatenates adjacent string literals (e.g., `"foo" "bar"` is rewritten to `"foobar"`).
+    ConcatStrings,
+
+    /// Removes the parentheses enclosing a single variable or literal in function definitions (e.g., `(X) + 1`).
+    ///
+    /// Only the clause heads, guards and bodies of function definitions are rewritten.
+    /// The parentheses in other forms (e.g., `-define` or `-record`) and in macro calls are kept as they are.
+    RedundantParens,
+
+    /// Sorts the items of `-export` and `-export_type` lists by name and arity, removes duplicates,
+    /// and merges consecutive attributes of the same kind into one.
+    ///
+    /// Attributes separated by blank lines are kept apart, as they usually are separate export groups.
+    SortExports,
+
+    /// Sorts adjacent `-include` (or `-include_lib`) directives by their paths, and removes duplicates.
+    ///
+    /// Directives are never moved across other forms (e.g., `-define` or `-ifdef`), comments or directives of the other kind.
+    /// Note that this assumes that the included files in a run do not depend on each other.
+    SortIncludes,
+}
+
+impl RewriteRule {
+    pub const ALL: [Self; 5] = [
+        Self::SpecParens,
+        Self::ConcatStrings,
+        Self::RedundantParens,
+        Self::SortExports,
+        Self::SortIncludes,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SpecParens => "spec-parens",
+            Self::RedundantParens => "redundant-parens",
+            Self::ConcatStrings => "concat-strings",
+            Self::SortExports => "sort-exports",
+            Self::SortIncludes => "sort-includes",
+        }
+    }
+
+    pub(crate) fn get(self) -> &'static dyn Rewrite {
+        match self {
+            Self::SpecParens => &attrs::SpecParens,
+            Self::RedundantParens => &parens::RedundantParens,
+            Self::ConcatStrings => &strings::ConcatStrings,
+            Self::SortExports => &exports::SortExports,
+            Self::SortIncludes => &includes::SortIncludes,
+        }
+    }
+}
+
+impl FromStr for RewriteRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| {
+                let names = Self::ALL.map(Self::name);
+                anyhow::anyhow!(
+                    "unknown rewrite rule {:?} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for RewriteRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A parsed module and its text.
+#[derive(Debug)]
+pub(crate) struct Source<'a> {
+    pub text: &'a str,
+    pub path: Option<&'a Path>,
+    pub module: Module,
+}
+
+impl Source<'_> {
+    /// Returns the tokens of the text except whitespaces and comments.
+    ///
+    /// Note that triple-quoted strings and sigils are masked (see [Self::token_text]).
+    pub fn visible_tokens(&self) -> anyhow::Result<Vec<Token>> {
+        Tokenizer::new(&*crate::parse::mask_unsupported_syntax(
+            self.text, self.path,
+        ))
+        .filter(|x| !matches!(x, Ok(Token::Whitespace(_) | Token::Comment(_))))
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::from)
+    }
+
+    /// Returns `true` if there are comments in the text of `item`.
+    pub fn has_comment(&self, item: &impl Span) -> bool {
+        let text = &self.text[item.start_position().offset()..item.end_position().offset()];
+        Tokenizer::new(&*crate::parse::mask_unsupported_syntax(text, self.path))
+            .any(|x| matches!(x, Ok(Token::Comment(_))))
+    }
+
+    /// Returns the source text of `token`.
+    pub fn token_text(&self, token: &Token) -> &str {
+        &self.text[token.start_position().offset()..token.end_position().offset()]
+    }
+
+    /// Returns `true` if `token` is a triple-quoted string or a sigil.
+    pub fn is_verbatim_string(&self, token: &Token) -> bool {
+        let text = self.token_text(token);
+        matches!(token, Token::String(_)) && (text.starts_with('~') || text.starts_with("\"\"\""))
+    }
+}
+
+pub(crate) trait Rewrite {
+    /// Returns the edits to be applied to `source`.
+    fn rewrite(&self, source: &Source) -> Vec<Edit>;
+
+    /// Checks whether `rewritten` is equivalent to `original` in terms of this rule.
+    fn check(&self, original: &Source, rewritten: &Source) -> anyhow::Result<()>;
+}
+
+/// A replacement of `range` of the original text with `text`.
+#[derive(Debug, Clone)]
+pub(crate) struct Edit {
+    range: Range<usize>,
+    text: String,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+}
+
+/// Applies `edits` (that must not overlap) to `text`.
+pub(crate) fn apply_edits(text: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|x| x.range.start);
+    let mut rewritten = String::with_capacity(text.len());
+    let mut offset = 0;
+    for edit in edits {
+        rewritten.push_str(&text[offset..edit.range.start]);
+        rewritten.push_str(&edit.text);
+        offset = edit.range.end;
+    }
+    rewritten.push_str(&text[offset..]);
+    rewritten
+}
+
+/// Returns `true` for each token that is a part of a macro call (e.g., `?FOO(Arg)`).
+///
+/// Rewriting such tokens could change the result of the macro expansion (e.g., stringified arguments).
+fn macro_call_flags(tokens: &[Token]) -> Vec<bool> {
+    let is_symbol = |i: usize, symbol: Symbol| matches!(tokens.get(i), Some(Token::Symbol(x)) if x.value() == symbol);
+
+    let mut flags = vec![false; tokens.len()];
+    let mut i = 0;
+    while i < tokens.len() {
+        if !is_symbol(i, Symbol::Question) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 2;
+        if is_symbol(i, Symbol::OpenParen) {
+            let mut depth = 0;
+            while i < tokens.len() {
+                if is_symbol(i, Symbol::OpenParen) {
+                    depth += 1;
+                } else if is_symbol(i, Symbol::CloseParen) {
+                    depth -= 1;
+                    if depth == 0 {
+                        i += 1;
+                        break;
+                    }
+                }
+                i += 1;
+            }
+        }
+        for flag in &mut flags[start..i.min(tokens.len())] {
+            *flag = true;
+        }
+    }
+    flags
+}
+
+/// Returns the range of the tokens located in `range` of the text.
+fn token_range(tokens: &[Token], range: Range<usize>) -> Range<usize> {
+    let start = tokens.partition_point(|x| x.start_position().offset() < range.start);
+    let end = tokens.partition_point(|x| x.start_position().offset() < range.end);
+    start..end
+}
+
+/// Checks that `original` and `rewritten` have the same token texts after `normalize` is applied to both.
+///
+/// `normalize` should undo only the kind of change made by a rule (e.g., removing redundant parentheses)
+/// without depending on how the rule selected the places to change, so that any other change is detected.
+fn check_normalized_tokens<F>(
+    original: &Source,
+    rewritten: &Source,
+    normalize: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(&Source, &[Token]) -> Vec<String>,
+{
+    let original_tokens = normalize(original, &original.visible_tokens()?);
+    let rewritten_tokens = normalize(rewritten, &rewritten.visible_tokens()?);
+    if let Some((i, (x, y))) = original_tokens
+        .iter()
+        .zip(&rewritten_tokens)
+        .enumerate()
+        .find(|(_, (x, y))| x != y)
+    {
+        anyhow::bail!(
+            "unexpected tokens were changed: the {}-th token {:?} became {:?}",
+            i,
+            x,
+            y
+        );
+    }
+    anyhow::ensure!(
+        original_tokens.len() == rewritten_tokens.len(),
+        "unexpected tokens were added or removed"
+    );
+    Ok(())
+}
+
+// Returns `true` if there are only whitespace characters between the two items.
+fn is_adjacent(text: &str, prev: &impl Span, next: &impl Span) -> bool {
+    text[prev.end_position().offset()..next.start_position().offset()]
+        .trim()
+        .is_empty()
+}
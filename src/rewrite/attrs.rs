@@ -0,0 +1,99 @@
+use crate::items::Form;
+use crate::rewrite::{Edit, Rewrite, Source};
+use crate::span::Span;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::Token;
+use std::collections::HashSet;
+
+/// See [crate::rewrite::RewriteRule::SpecParens].
+#[derive(Debug)]
+pub(super) struct SpecParens;
+
+impl SpecParens {
+    fn parens(source: &Source) -> Vec<(usize, usize)> {
+        source
+            .module
+            .forms()
+            .iter()
+            .filter_map(Form::spec_or_type_parens)
+            .map(|(open, close)| {
+                (
+                    open.start_position().offset(),
+                    close.start_position().offset(),
+                )
+            })
+            .filter(|(open, close)| {
+                // The parentheses may come from a macro expansion.
+                source.text[*open..].starts_with('(') && source.text[*close..].starts_with(')')
+            })
+            .collect()
+    }
+}
+
+impl Rewrite for SpecParens {
+    fn rewrite(&self, source: &Source) -> Vec<Edit> {
+        Self::parens(source)
+            .into_iter()
+            .flat_map(|(open, close)| {
+                // A space prevents the attribute name from being joined with the following token.
+                [
+                    Edit::new(open..open + 1, " "),
+                    Edit::new(close..close + 1, ""),
+                ]
+            })
+            .collect()
+    }
+
+    fn check(&self, original: &Source, rewritten: &Source) -> anyhow::Result<()> {
+        super::check_normalized_tokens(original, rewritten, remove_value_parens)?;
+        anyhow::ensure!(
+            Self::parens(rewritten).is_empty(),
+            "some parentheses were not removed"
+        );
+        Ok(())
+    }
+}
+
+/// Returns the token texts without the parentheses enclosing the whole value of
+/// `-spec`, `-callback`, `-type` or `-opaque` attributes.
+fn remove_value_parens(source: &Source, tokens: &[Token]) -> Vec<String> {
+    let is_symbol = |i: usize, symbol: Symbol| matches!(tokens.get(i), Some(Token::Symbol(x)) if x.value() == symbol);
+
+    let mut removed = HashSet::new();
+    for i in 0..tokens.len() {
+        let is_form_start = i == 0 || is_symbol(i - 1, Symbol::Dot);
+        let is_target = matches!(
+            tokens.get(i + 1),
+            Some(Token::Atom(x)) if matches!(x.value(), "spec" | "callback" | "type" | "opaque")
+        );
+        if !(is_form_start
+            && is_symbol(i, Symbol::Hyphen)
+            && is_target
+            && is_symbol(i + 2, Symbol::OpenParen))
+        {
+            continue;
+        }
+
+        let mut depth = 0;
+        for j in i + 2..tokens.len() {
+            if is_symbol(j, Symbol::OpenParen) {
+                depth += 1;
+            } else if is_symbol(j, Symbol::CloseParen) {
+                depth -= 1;
+                if depth == 0 {
+                    if is_symbol(j + 1, Symbol::Dot) {
+                        removed.insert(i + 2);
+                        removed.insert(j);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !removed.contains(i))
+        .map(|(_, x)| source.token_text(x).to_owned())
+        .collect()
+}
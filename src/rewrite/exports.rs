@@ -0,0 +1,137 @@
+use crate::items::forms::Attr;
+use crate::items::Form;
+use crate::rewrite::{Edit, Rewrite, Source};
+use crate::span::Span;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Token, Tokenizer};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// See [crate::rewrite::RewriteRule::SortExports].
+///
+/// Attributes containing macros or comments are left untouched.
+#[derive(Debug)]
+pub(super) struct SortExports;
+
+impl Rewrite for SortExports {
+    fn rewrite(&self, source: &Source) -> Vec<Edit> {
+        let text = source.text;
+        let mut edits = Vec::new();
+        let mut group: Vec<(&Attr, Vec<NameAndArity>)> = Vec::new();
+        for form in source.module.forms() {
+            let attr = form.as_attr();
+            let items = attr.and_then(|x| export_items(source, x));
+            let (Some(attr), Some(items)) = (attr, items) else {
+                edits.extend(sort_group(&group));
+                group.clear();
+                continue;
+            };
+            if let Some((last, _)) = group.last() {
+                if last.name() != attr.name() || !is_consecutive(text, last, attr) {
+                    edits.extend(sort_group(&group));
+                    group.clear();
+                }
+            }
+            group.push((attr, items));
+        }
+        edits.extend(sort_group(&group));
+        edits
+    }
+
+    fn check(&self, original: &Source, rewritten: &Source) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            exports(original) == exports(rewritten),
+            "the exported items were changed"
+        );
+        Ok(())
+    }
+}
+
+// Returns `true` if there are only whitespace characters without blank lines between the two attributes.
+//
+// Blank lines usually separate export groups on purpose, so such attributes are not merged.
+fn is_consecutive(text: &str, prev: &Attr, next: &Attr) -> bool {
+    super::is_adjacent(text, prev, next)
+        && text[prev.end_position().offset()..next.start_position().offset()]
+            .matches('\n')
+            .count()
+            <= 1
+}
+
+/// Returns the `name/arity` pairs exported by each `-export` and `-export_type` attribute name.
+fn exports(source: &Source) -> BTreeMap<String, BTreeSet<(String, u32)>> {
+    let mut exports = BTreeMap::<_, BTreeSet<_>>::new();
+    for attr in source.module.forms().iter().filter_map(Form::as_attr) {
+        if let Some(items) = export_items(source, attr) {
+            exports
+                .entry(attr.name().to_owned())
+                .or_default()
+                .extend(items.into_iter().map(|x| (x.name, x.arity)));
+        }
+    }
+    exports
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NameAndArity {
+    name: String,
+    arity: u32,
+    text: String,
+}
+
+fn export_items(source: &Source, attr: &Attr) -> Option<Vec<NameAndArity>> {
+    let text = source.text;
+    if !matches!(attr.name(), "export" | "export_type") || source.has_comment(attr) {
+        return None;
+    }
+    attr.list_value()?
+        .iter()
+        .map(|item| {
+            item.get().as_name_and_arity()?;
+
+            // The item may come from a macro expansion, so its source text is checked as well.
+            let item_text = &text[item.start_position().offset()..item.end_position().offset()];
+            let tokens = Tokenizer::new(item_text)
+                .filter(|x| !matches!(x, Ok(Token::Whitespace(_))))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            match &tokens[..] {
+                [Token::Atom(name), Token::Symbol(slash), Token::Integer(arity)]
+                    if slash.value() == Symbol::Slash =>
+                {
+                    Some(NameAndArity {
+                        name: name.value().to_owned(),
+                        arity: arity.text().parse().ok()?,
+                        text: format!("{}/{}", name.text(), arity.text()),
+                    })
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn sort_group(group: &[(&Attr, Vec<NameAndArity>)]) -> Option<Edit> {
+    let (first, _) = group.first()?;
+    let (last, _) = group.last()?;
+    let original = group
+        .iter()
+        .flat_map(|(_, items)| items.iter())
+        .collect::<Vec<_>>();
+
+    let mut sorted = original.clone();
+    sorted.sort_by(|a, b| (&a.name, a.arity).cmp(&(&b.name, b.arity)));
+    sorted.dedup_by(|a, b| (&a.name, a.arity) == (&b.name, b.arity));
+    if group.len() == 1 && sorted == original {
+        return None;
+    }
+
+    let items = sorted
+        .iter()
+        .map(|x| x.text.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(Edit::new(
+        first.start_position().offset()..last.end_position().offset(),
+        format!("-{}([{}]).", first.name(), items),
+    ))
+}
@@ -0,0 +1,108 @@
+use crate::items::forms::IncludeDirective;
+use crate::rewrite::{Edit, Rewrite, Source};
+use crate::span::Span;
+
+/// See [crate::rewrite::RewriteRule::SortIncludes].
+///
+/// Only runs of adjacent directives of the same kind are sorted.
+/// A run ends at any other form (e.g., `-define` or `-ifdef`), a comment or a directive of the other kind,
+/// so that a directive is never moved across something that could affect the included files.
+#[derive(Debug)]
+pub(super) struct SortIncludes;
+
+impl Rewrite for SortIncludes {
+    fn rewrite(&self, source: &Source) -> Vec<Edit> {
+        include_runs(source)
+            .iter()
+            .filter_map(|run| sort_run(source.text, run))
+            .collect()
+    }
+
+    fn check(&self, original: &Source, rewritten: &Source) -> anyhow::Result<()> {
+        let original_runs = include_runs(original);
+        let rewritten_runs = include_runs(rewritten);
+        anyhow::ensure!(
+            original_runs.len() == rewritten_runs.len(),
+            "the runs of the include directives were changed"
+        );
+        for (original_run, rewritten_run) in original_runs.iter().zip(&rewritten_runs) {
+            let original_keys = original_run
+                .iter()
+                .map(|x| include_key(x))
+                .collect::<Vec<_>>();
+            let rewritten_keys = rewritten_run
+                .iter()
+                .map(|x| include_key(x))
+                .collect::<Vec<_>>();
+            anyhow::ensure!(
+                rewritten_keys == original_keys || rewritten_keys == sorted_keys(&original_keys),
+                "the included files were changed: {:?} => {:?}",
+                original_keys,
+                rewritten_keys
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the runs of adjacent `-include` (or `-include_lib`) directives that can be sorted.
+fn include_runs<'a>(source: &'a Source) -> Vec<Vec<&'a IncludeDirective>> {
+    let text = source.text;
+    let mut runs = Vec::new();
+    let mut run: Vec<&IncludeDirective> = Vec::new();
+    for form in source.module.forms() {
+        let Some(include) = form.as_include().filter(|x| !source.has_comment(*x)) else {
+            runs.push(std::mem::take(&mut run));
+            continue;
+        };
+        if let Some(last) = run.last() {
+            if last.is_include_lib() != include.is_include_lib()
+                || !super::is_adjacent(text, *last, include)
+            {
+                runs.push(std::mem::take(&mut run));
+            }
+        }
+        run.push(include);
+    }
+    runs.push(run);
+    runs.retain(|run| !run.is_empty());
+    runs
+}
+
+fn include_key(include: &IncludeDirective) -> (bool, &str) {
+    (include.is_include_lib(), include.path())
+}
+
+fn sorted_keys<'a>(keys: &[(bool, &'a str)]) -> Vec<(bool, &'a str)> {
+    let mut keys = keys.to_vec();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+fn sort_run(text: &str, run: &[&IncludeDirective]) -> Option<Edit> {
+    let first = run.first()?;
+    let last = run.last()?;
+
+    let mut sorted = run.to_vec();
+    sorted.sort_by_key(|x| include_key(x));
+    sorted.dedup_by_key(|x| include_key(x));
+    if sorted.len() == run.len()
+        && sorted
+            .iter()
+            .zip(run)
+            .all(|(a, b)| a.start_position() == b.start_position())
+    {
+        return None;
+    }
+
+    let directives = sorted
+        .iter()
+        .map(|x| &text[x.start_position().offset()..x.end_position().offset()])
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(Edit::new(
+        first.start_position().offset()..last.end_position().offset(),
+        directives,
+    ))
+}
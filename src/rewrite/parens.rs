@@ -0,0 +1,136 @@
+use crate::rewrite::{Edit, Rewrite, Source};
+use crate::span::Span;
+use erl_tokenize::values::{Keyword, Symbol};
+use erl_tokenize::{PositionRange as _, Token};
+use std::collections::HashSet;
+
+/// See [crate::rewrite::RewriteRule::RedundantParens].
+///
+/// Parentheses in macro calls are left untouched.
+#[derive(Debug)]
+pub(super) struct RedundantParens;
+
+impl RedundantParens {
+    /// Returns the token indices of the open parentheses to be removed.
+    fn parens(source: &Source, tokens: &[Token]) -> Vec<usize> {
+        let is_macro_call = super::macro_call_flags(tokens);
+        let is_symbol = |i: usize, symbol: Symbol| matches!(&tokens[i], Token::Symbol(x) if x.value() == symbol);
+
+        let mut parens = Vec::new();
+        for form in source.module.forms().iter().filter(|x| x.is_fun_decl()) {
+            let range = super::token_range(
+                tokens,
+                form.start_position().offset()..form.end_position().offset(),
+            );
+            for i in range.start + 1..range.end.saturating_sub(2) {
+                if is_macro_call[i]
+                    || !is_symbol(i, Symbol::OpenParen)
+                    || !is_symbol(i + 2, Symbol::CloseParen)
+                {
+                    continue;
+                }
+                let is_single_term = matches!(
+                    &tokens[i + 1],
+                    Token::Atom(_)
+                        | Token::Char(_)
+                        | Token::Float(_)
+                        | Token::Integer(_)
+                        | Token::String(_)
+                        | Token::Variable(_)
+                );
+                // Otherwise, the parentheses could be a part of a function call or a `fun` expression.
+                let is_grouping = match &tokens[i - 1] {
+                    Token::Symbol(x) => !matches!(
+                        x.value(),
+                        Symbol::CloseParen
+                            | Symbol::CloseSquare
+                            | Symbol::CloseBrace
+                            | Symbol::DoubleRightAngle
+                    ),
+                    Token::Keyword(x) => !matches!(x.value(), Keyword::Fun | Keyword::End),
+                    _ => false,
+                };
+                if is_single_term && is_grouping {
+                    parens.push(i);
+                }
+            }
+        }
+        parens
+    }
+}
+
+impl Rewrite for RedundantParens {
+    fn rewrite(&self, source: &Source) -> Vec<Edit> {
+        let Ok(tokens) = source.visible_tokens() else {
+            return Vec::new();
+        };
+
+        // A space is inserted if the parenthesis is between two tokens that could be joined (e.g., `case(X)of`).
+        let replacement = |prev: &Token, next: &Token| {
+            let is_adjacent = prev.end_position().offset() == next.start_position().offset() - 1;
+            if is_adjacent && !matches!(prev, Token::Symbol(_)) && !matches!(next, Token::Symbol(_))
+            {
+                " "
+            } else {
+                ""
+            }
+        };
+        Self::parens(source, &tokens)
+            .into_iter()
+            .flat_map(|i| {
+                let open = tokens[i].start_position().offset();
+                let close = tokens[i + 2].start_position().offset();
+                let mut edits = vec![Edit::new(
+                    open..open + 1,
+                    replacement(&tokens[i - 1], &tokens[i + 1]),
+                )];
+                let text = tokens
+                    .get(i + 3)
+                    .map_or("", |next| replacement(&tokens[i + 1], next));
+                edits.push(Edit::new(close..close + 1, text));
+                edits
+            })
+            .collect()
+    }
+
+    fn check(&self, original: &Source, rewritten: &Source) -> anyhow::Result<()> {
+        super::check_normalized_tokens(original, rewritten, remove_grouping_parens)
+    }
+}
+
+/// Returns the token texts without the parentheses that enclose a single non-symbol token and
+/// don't follow a token that could be called (e.g., `f(X)`, `fun(X)` or `?M(X)`).
+fn remove_grouping_parens(source: &Source, tokens: &[Token]) -> Vec<String> {
+    let is_macro_call = super::macro_call_flags(tokens);
+    let is_symbol = |i: usize, symbol: Symbol| matches!(tokens.get(i), Some(Token::Symbol(x)) if x.value() == symbol);
+    let is_callee = |i: usize| match &tokens[i] {
+        Token::Symbol(x) => matches!(
+            x.value(),
+            Symbol::CloseParen
+                | Symbol::CloseSquare
+                | Symbol::CloseBrace
+                | Symbol::DoubleRightAngle
+        ),
+        Token::Keyword(x) => matches!(x.value(), Keyword::Fun | Keyword::End),
+        _ => true,
+    };
+
+    let mut removed = HashSet::new();
+    for i in 0..tokens.len() {
+        if is_symbol(i, Symbol::OpenParen)
+            && is_symbol(i + 2, Symbol::CloseParen)
+            && !matches!(tokens[i + 1], Token::Symbol(_) | Token::Keyword(_))
+            && !is_macro_call[i]
+            && !(i > 0 && is_callee(i - 1))
+        {
+            removed.insert(i);
+            removed.insert(i + 2);
+        }
+    }
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !removed.contains(i))
+        .map(|(_, x)| source.token_text(x).to_owned())
+        .collect()
+}
@@ -0,0 +1,114 @@
+use crate::rewrite::{Edit, Rewrite, Source};
+use crate::span::Span;
+use erl_tokenize::{PositionRange as _, Token, Tokenizer};
+use std::ops::Range;
+
+/// See [crate::rewrite::RewriteRule::ConcatStrings].
+///
+/// Strings in `-define` and `-include` directives, macro calls, or with comments between them are left untouched.
+#[derive(Debug)]
+pub(super) struct ConcatStrings;
+
+impl ConcatStrings {
+    /// Returns the token index ranges of the strings to be concatenated, and the concatenated strings.
+    fn runs(source: &Source, tokens: &[Token]) -> Vec<(Range<usize>, String)> {
+        let is_macro_call = super::macro_call_flags(tokens);
+        let is_plain_string = |i: usize| {
+            matches!(&tokens[i], Token::String(_))
+                && !source.is_verbatim_string(&tokens[i])
+                && !is_macro_call[i]
+        };
+
+        let mut runs = Vec::new();
+        for form in source.module.forms().iter().filter(|x| !x.is_directive()) {
+            let range = super::token_range(
+                tokens,
+                form.start_position().offset()..form.end_position().offset(),
+            );
+            let mut i = range.start;
+            while i < range.end {
+                let start = i;
+                while i < range.end
+                    && is_plain_string(i)
+                    && (i == start
+                        || source.text[tokens[i - 1].end_position().offset()
+                            ..tokens[i].start_position().offset()]
+                            .trim()
+                            .is_empty())
+                {
+                    i += 1;
+                }
+                if i - start > 1 {
+                    if let Some(concatenated) = concat(&tokens[start..i]) {
+                        runs.push((start..i, concatenated));
+                    }
+                }
+                i = i.max(start + 1);
+            }
+        }
+        runs
+    }
+}
+
+// Returns `None` if the concatenated text has a different value (e.g., `"\x4" "1"` is not `"\x41"`).
+fn concat(tokens: &[Token]) -> Option<String> {
+    let mut text = String::from("\"");
+    let mut value = String::new();
+    for token in tokens {
+        let Token::String(x) = token else {
+            return None;
+        };
+        text.push_str(&x.text()[1..x.text().len() - 1]);
+        value.push_str(x.value());
+    }
+    text.push('"');
+
+    let mut tokenizer = Tokenizer::new(&text);
+    match (tokenizer.next(), tokenizer.next()) {
+        (Some(Ok(Token::String(x))), None) if x.value() == value => Some(text),
+        _ => None,
+    }
+}
+
+impl Rewrite for ConcatStrings {
+    fn rewrite(&self, source: &Source) -> Vec<Edit> {
+        let Ok(tokens) = source.visible_tokens() else {
+            return Vec::new();
+        };
+        Self::runs(source, &tokens)
+            .into_iter()
+            .map(|(range, concatenated)| {
+                let start = tokens[range.start].start_position().offset();
+                let end = tokens[range.end - 1].end_position().offset();
+                Edit::new(start..end, concatenated)
+            })
+            .collect()
+    }
+
+    fn check(&self, original: &Source, rewritten: &Source) -> anyhow::Result<()> {
+        super::check_normalized_tokens(original, rewritten, concat_string_values)
+    }
+}
+
+/// Returns the token texts where each run of adjacent plain strings (except in macro calls) is
+/// replaced with the escaped value of the concatenated string.
+fn concat_string_values(source: &Source, tokens: &[Token]) -> Vec<String> {
+    let is_macro_call = super::macro_call_flags(tokens);
+    let mut normalized = Vec::new();
+    let mut last_string: Option<String> = None;
+    for (token, is_macro_call) in tokens.iter().zip(is_macro_call) {
+        match token {
+            Token::String(x) if !is_macro_call && !source.is_verbatim_string(token) => {
+                last_string
+                    .get_or_insert_with(String::new)
+                    .push_str(x.value());
+            }
+            _ => {
+                normalized.extend(last_string.take().map(|x| format!("{:?}", x)));
+                normalized.push(source.token_text(token).to_owned());
+            }
+        }
+    }
+    normalized.extend(last_string.take().map(|x| format!("{:?}", x)));
+    normalized
+}
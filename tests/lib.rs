@@ -1,11 +1,16 @@
-use efmt::items::Module;
+use efmt::format::ColumnWidth;
+use efmt::items::{Config, Module, ModuleOrConfig};
+use efmt::rewrite::RewriteRule;
 
 #[test]
 fn format_works() -> anyhow::Result<()> {
     for entry in std::fs::read_dir("tests/testdata/")? {
         let entry = entry?;
         let path = entry.path();
-        if path.extension().map_or(true, |ext| ext != "erl") {
+        if path
+            .extension()
+            .is_none_or(|ext| ext != "erl" && ext != "escript")
+        {
             continue;
         }
         let formatted = efmt::Options::new()
@@ -16,3 +21,495 @@ fn format_works() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn format_config_template_works() -> anyhow::Result<()> {
+    let path = "tests/testdata/sys.config.src";
+    let formatted = efmt::Options::new()
+        .max_columns(50)
+        .format_file::<Config, _>(path)?;
+    let expected = std::fs::read_to_string(path)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
+
+#[test]
+fn format_text_with_path_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("foo.hrl"), "-define(BEGIN, begin).")?;
+    let text = indoc::indoc! {"
+    -include(\"foo.hrl\").
+    f() -> ?BEGIN ok end.
+    "};
+    let expected = indoc::indoc! {"
+    -include(\"foo.hrl\").
+    f() ->
+        ?BEGIN
+            ok
+        end.
+    "};
+
+    // The text can be parsed only if the include file is found.
+    assert!(efmt::Options::new().format_text::<Module>(text).is_err());
+    let formatted = efmt::Options::new()
+        .format_text_with_path::<Module, _>(text, dir.path().join("foo.erl"))?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
+
+#[test]
+fn macro_expansion_notes_work() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let header = dir.path().join("foo.hrl");
+    std::fs::write(&header, "%% Bad macros.\n-define(BAD, (a b)).")?;
+    let text = indoc::indoc! {"
+    -include(\"foo.hrl\").
+    f() -> ?BAD.
+    "};
+
+    let err = efmt::Options::new()
+        .format_text_with_path::<Module, _>(text, dir.path().join("foo.erl"))
+        .err()
+        .unwrap();
+    let note = format!(
+        "= note: in expansion of ?BAD (defined at {}:2)",
+        header.display()
+    );
+    assert!(err.to_string().ends_with(&note), "{err}");
+    Ok(())
+}
+
+#[test]
+fn missing_macros_work() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let text = indoc::indoc! {"
+    -include(\"missing.hrl\").
+    f() -> ?FOO.
+    "};
+    let path = dir.path().join("foo.erl");
+
+    let report = efmt::missing::MissingReport::new();
+    efmt::Options::new()
+        .report_missing(report.clone())
+        .format_text_with_path::<Module, _>(text, &path)?;
+    let expected = format!(
+        "Unresolved include files (1):\n- missing.hrl\n  - {0}\n\nUndefined macros (1):\n- ?FOO\n  - {0}\n",
+        path.display()
+    );
+    similar_asserts::assert_str_eq!(report.to_string(), expected);
+
+    let err = efmt::Options::new()
+        .deny_missing_macros()
+        .format_text_with_path::<Module, _>(text, &path)
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().ends_with("^ undefined macro `?FOO`"),
+        "{err}"
+    );
+
+    assert!(efmt::Options::new()
+        .deny_missing_macros()
+        .format_text_with_path::<Module, _>("f() -> ?MODULE.\n", &path)
+        .is_ok());
+    Ok(())
+}
+
+#[test]
+fn explain_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("foo.erl");
+    std::fs::write(&path, "foo() -> Foo = bar(baz, qux, quux), Foo.\n")?;
+
+    let explanation = efmt::Options::new()
+        .max_columns(25)
+        .explain_file::<Module, _>(&path, 3)?;
+    let lines = explanation.lines().collect::<Vec<_>>();
+    assert_eq!(lines[1], "3 |         bar(baz,");
+    assert!(
+        lines[2].ends_with("FunDecl > FunctionClause > Body > Expr > BinaryOpCallExpr(=) > Expr > FunctionCallExpr"),
+        "{explanation}"
+    );
+    assert!(
+        lines.contains(&concat!(
+            "- BinaryOpCallExpr(=): indent=Offset(4) (column 8), newline=IfTooLongOrMultiLine, ",
+            "fell back due to MultiLine, put a newline"
+        )),
+        "{explanation}"
+    );
+
+    // The line is shown as it is in the formatted text (i.e., with the trailing comments aligned).
+    std::fs::write(&path, "-record(foo, {a, % a\n bbbb % b\n}).\n")?;
+    let explanation = efmt::Options::new()
+        .align_trailing_comments()
+        .explain_file::<Module, _>(&path, 2)?;
+    let lines = explanation.lines().collect::<Vec<_>>();
+    assert_eq!(lines[1], "2 |         {a,    % a", "{explanation}");
+    Ok(())
+}
+
+#[test]
+fn column_width_works() -> anyhow::Result<()> {
+    // "日本語" takes 6 columns, 3 chars or 9 bytes.
+    let text = indoc::indoc! {"
+    foo() ->
+        {\"日本語\", bar, baz}.
+    "};
+    let wrapped = indoc::indoc! {"
+    foo() ->
+        {\"日本語\", bar,
+         baz}.
+    "};
+    let format = |text: &str, column_width: ColumnWidth, max_columns| {
+        efmt::Options::new()
+            .max_columns(max_columns)
+            .column_width(column_width)
+            .format_text::<Module>(text)
+    };
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Unicode, 25)?, text);
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Unicode, 24)?, wrapped);
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Chars, 22)?, text);
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Chars, 21)?, wrapped);
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Bytes, 28)?, text);
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Bytes, 27)?, wrapped);
+
+    // Combining characters don't take a column by themselves.
+    let text = "foo() ->\n    {\"e\u{301}\u{301}\u{301}\", bar, baz}.\n";
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Unicode, 20)?, text);
+
+    // Items are aligned by the display width.
+    let text = indoc::indoc! {"
+    foo() ->
+        \"日本語\" ++ bar(bazzzz,
+                        quxxxx).
+    "};
+    similar_asserts::assert_str_eq!(format(text, ColumnWidth::Unicode, 30)?, text);
+    Ok(())
+}
+
+#[test]
+fn allow_partial_works() -> anyhow::Result<()> {
+    let text = indoc::indoc! {"
+    foo() -> ok.
+    bar() -> [a,
+      b  % comment
+      c].
+    baz() -> ok.
+    qux() -> \"unclosed.
+    quux() -> ok.
+    "};
+    let expected = indoc::indoc! {"
+    foo() ->
+        ok.
+    bar() -> [a,
+      b  % comment
+      c].
+    baz() ->
+        ok.
+    qux() -> \"unclosed.
+    quux() -> ok.
+    "};
+    assert!(efmt::Options::new().format_text::<Module>(text).is_err());
+
+    let formatted = efmt::Options::new()
+        .allow_partial()
+        .format_text::<Module>(text)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+
+    // A config file is not parsed as a module consisting of broken forms.
+    let formatted = efmt::Options::new()
+        .allow_partial()
+        .format_text::<ModuleOrConfig>("[{a,   1}].\n")?;
+    similar_asserts::assert_str_eq!(formatted, "[{a, 1}].\n");
+
+    // The first form can also be kept.
+    let formatted = efmt::Options::new()
+        .allow_partial()
+        .format_text::<ModuleOrConfig>("foo( -> ok.\nbar() -> ok.\n")?;
+    similar_asserts::assert_str_eq!(formatted, "foo( -> ok.\nbar() ->\n    ok.\n");
+    Ok(())
+}
+
+#[test]
+fn align_trailing_comments_works() -> anyhow::Result<()> {
+    let text = indoc::indoc! {"
+    -record(foo,
+            {a = 1 :: integer(),  % first
+             bbbbbbbb :: atom(),  % second
+             c}).  % third
+
+    -define(A, 1).  % one
+    -define(LONGER_NAME, 2).  % two
+
+    f() ->
+        X = 1,  % x
+        Y = 2,  % this comment just reaches the limit.
+        {X, Y}.  % tuple
+
+    g() ->
+        ok.  % the next form is not aligned
+    -define(B, 2).  % two
+    "};
+    let expected = indoc::indoc! {"
+    -record(foo,
+            {a = 1 :: integer(),  % first
+             bbbbbbbb :: atom(),  % second
+             c}).                 % third
+
+    -define(A, 1).            % one
+    -define(LONGER_NAME, 2).  % two
+
+    f() ->
+        X = 1,  % x
+        Y = 2,  % this comment just reaches the limit.
+        {X, Y}.  % tuple
+
+    g() ->
+        ok.  % the next form is not aligned
+    -define(B, 2).  % two
+    "};
+    let formatted = efmt::Options::new()
+        .max_columns(50)
+        .align_trailing_comments()
+        .format_text::<Module>(text)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
+
+#[test]
+fn reflow_comments_works() -> anyhow::Result<()> {
+    let text = indoc::indoc! {"
+    %%% A module level banner is never reflowed even if it is too long.
+
+    %% @doc This is a long documentation comment that exceeds the limit.
+    %% The second line.
+    %%
+    %% @param X a parameter
+    %%   an indented line is kept as it is, even if it is too long.
+    f(X) ->
+        %% a long comment in a function body that exceeds the limit
+        % a single `%` comment is never reflowed even if it is too long
+        X.
+
+    g() ->
+        receive
+            %% a block in a region that is retried in multi-line mode
+            %% is written only once
+            {ok, X} -> X;
+            _ -> error
+        end.
+
+    %% efmt:off
+    %% this comment is not reflowed because efmt is turned off here.
+    %% efmt:on
+    "};
+    let expected = indoc::indoc! {"
+    %%% A module level banner is never reflowed even if it is too long.
+
+    %% @doc This is a long documentation comment that
+    %% exceeds the limit. The second line.
+    %%
+    %% @param X a parameter
+    %%   an indented line is kept as it is, even if it is too long.
+    f(X) ->
+        %% a long comment in a function body that
+        %% exceeds the limit
+        % a single `%` comment is never reflowed even if it is too long
+        X.
+
+    g() ->
+        receive
+            %% a block in a region that is retried in
+            %% multi-line mode is written only once
+            {ok, X} ->
+                X;
+            _ ->
+                error
+        end.
+
+    %% efmt:off
+    %% this comment is not reflowed because efmt is turned off here.
+    %% efmt:on
+    "};
+    let formatted = efmt::Options::new()
+        .max_columns(50)
+        .reflow_comments()
+        .format_text::<Module>(text)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
+
+#[test]
+fn normalize_blank_lines_works() -> anyhow::Result<()> {
+    let text = indoc::indoc! {"
+    -module(foo).
+    -export([a/0]).
+    -export([b/0]).
+    a() ->
+
+        ok.
+    b() -> ok.
+    -doc \"\"\"
+      C.
+      \"\"\".
+
+    -spec c() -> ok.
+
+    c() -> ok.
+
+
+
+    %% A comment.
+    d() ->
+        case x of
+            y ->
+
+                z
+        end.
+
+    e() ->
+        fun (X) ->
+
+                X
+        end.
+    "};
+    let expected = indoc::indoc! {"
+    -module(foo).
+    -export([a/0]).
+    -export([b/0]).
+    a() ->
+        ok.
+
+    b() ->
+        ok.
+
+    -doc \"\"\"
+      C.
+      \"\"\".
+    -spec c() -> ok.
+    c() ->
+        ok.
+
+    %% A comment.
+    d() ->
+        case x of
+            y ->
+                z
+        end.
+
+    e() ->
+        fun (X) -> X end.
+    "};
+    let formatted = efmt::Options::new()
+        .max_columns(50)
+        .normalize_blank_lines()
+        .remove_clause_body_leading_blank_lines()
+        .format_text::<Module>(text)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
+
+#[test]
+fn sort_exports_works() -> anyhow::Result<()> {
+    let text = indoc::indoc! {"
+    -module(foo).
+    -export([foo/1, bar/0, foo/0]).
+    -export([bar/0, baz/2]).
+
+    -export_type([t/0, 'T'/1]).
+    %% A comment prevents merging.
+    -export_type([s/0]).
+    -export([?MODULE/0, a/0]).
+    -export([c/0, b/0]).
+
+    -export([d/0]).
+    -include_lib(\"kernel/include/file.hrl\").
+    -include(\"foo.hrl\").
+    -include(\"bar.hrl\").
+    -include(\"foo.hrl\").
+    -define(BAZ, true).
+    -include(\"baz.hrl\").
+    -ifdef(QUX).
+    -include(\"qux.hrl\").
+    -endif.
+    -include(\"a.hrl\").
+    "};
+    let expected = indoc::indoc! {"
+    -module(foo).
+    -export([bar/0, baz/2, foo/0, foo/1]).
+
+    -export_type(['T'/1, t/0]).
+    %% A comment prevents merging.
+    -export_type([s/0]).
+    -export([?MODULE/0, a/0]).
+    -export([b/0, c/0]).
+
+    -export([d/0]).
+    -include_lib(\"kernel/include/file.hrl\").
+    -include(\"bar.hrl\").
+    -include(\"foo.hrl\").
+    -define(BAZ, true).
+    -include(\"baz.hrl\").
+    -ifdef(QUX).
+    -include(\"qux.hrl\").
+    -endif.
+    -include(\"a.hrl\").
+    "};
+    let formatted = efmt::Options::new()
+        .max_columns(50)
+        .disable_include()
+        .rewrite(RewriteRule::SortExports)
+        .rewrite(RewriteRule::SortIncludes)
+        .format_text::<Module>(text)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
+
+#[test]
+fn rewrite_rules_work() -> anyhow::Result<()> {
+    let text = indoc::indoc! {r#"
+    -spec(foo(integer()) -> string()).
+    -type(t() :: atom()).
+    -callback(bar() -> ok).
+    foo(X) ->
+        case(X)of
+            (1) ->
+                ("a" "b");
+            _ when (X) > 2 ->
+                f((X)), "\1" "2";
+            _ ->
+                ?M((X), "a" "b"),
+                "c"  % comment
+                "d"
+        end.
+    "#};
+    let expected = indoc::indoc! {r#"
+    -spec foo(integer()) -> string().
+    -type t() :: atom().
+    -callback bar() -> ok.
+    foo(X) ->
+        case X of
+            1 ->
+                "ab";
+            _ when X > 2 ->
+                f(X),
+                "\1"
+                "2";
+            _ ->
+                ?M((X),
+                   "a"
+                   "b"),
+                "c"  % comment
+                "d"
+        end.
+    "#};
+    let formatted = efmt::Options::new()
+        .max_columns(50)
+        .disable_include()
+        .rewrite(RewriteRule::SpecParens)
+        .rewrite(RewriteRule::RedundantParens)
+        .rewrite(RewriteRule::ConcatStrings)
+        .format_text::<Module>(text)?;
+    similar_asserts::assert_str_eq!(formatted, expected);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+-module(deep_nested_terms).
+
+-export([config/0]).
+
+config() ->
+    {key_12,
+     #{name =>
+           {key_11,
+            #{name =>
+                  {key_10,
+                   #{name =>
+                         {key_9,
+                          #{name =>
+                                {key_8,
+                                 #{name =>
+                                       {key_7,
+                                        #{name =>
+                                              {key_6,
+                                               #{name =>
+                                                     {key_5,
+                                                      #{name =>
+                                                            {key_4,
+                                                             #{name =>
+                                                                   {key_3,
+                                                                    #{name =>
+                                                                          {key_2,
+                                                                           #{name =>
+                                                                                 {key_1,
+                                                                                  #{name =>
+                                                                                        leaf,
+                                                                                    values =>
+                                                                                        [1,
+                                                                                         1]}},
+                                                                             values =>
+                                                                                 [2,
+                                                                                  2]}},
+                                                                      values =>
+                                                                          [3,
+                                                                           3]}},
+                                                               values =>
+                                                                   [4,
+                                                                    4]}},
+                                                        values =>
+                                                            [5,
+                                                             5]}},
+                                                 values =>
+                                                     [6,
+                                                      6]}},
+                                          values =>
+                                              [7,
+                                               7]}},
+                                   values =>
+                                       [8, 8]}},
+                            values => [9, 9]}},
+                     values => [10, 10]}},
+              values => [11, 11]}},
+       values => [12, 12]}}.